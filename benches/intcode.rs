@@ -0,0 +1,229 @@
+//! Timings for the intcode interpreter.
+//!
+//! Run with `cargo bench`. There's no benchmark framework here, just a
+//! stopwatch around each case and the best of a few runs, which is plenty to
+//! compare approaches against each other.
+//!
+//! - `decode` compares decoding instructions with headers parsed through a
+//!   string (how the interpreter used to do it) against `read_instruction`.
+//! - `loop` runs a long counting loop with and without the decode cache, and
+//!   with the compiling engine.
+//! - `solve` runs the Day 2 noun/verb search over a synthetic program, once
+//!   for a reachable target and once for the full 10,000 pairs, both with the
+//!   decode cache and without it (the default for a search), then over a
+//!   program which symbolic analysis can solve directly.
+//! - `load` reads a large program from text and from the binary format.
+
+use aoc2019::intcode::search::Search;
+use aoc2019::intcode::{
+    binary, compiled, compute, parse_program, read_instruction, solve, IntcodeError, Machine, Op,
+    Param, Status,
+};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const RUNS: usize = 5;
+
+/// Runs `f` a few times and reports the fastest.
+fn bench<T>(name: &str, mut f: impl FnMut() -> T) -> Duration {
+    let best = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(f());
+            start.elapsed()
+        })
+        .min()
+        .unwrap();
+    println!("{:<24} {:>12.3?}", name, best);
+    best
+}
+
+fn speedup(name: &str, before: Duration, after: Duration) {
+    println!(
+        "{:<24} {:>11.2}x",
+        name,
+        before.as_secs_f64() / after.as_secs_f64()
+    );
+}
+
+/// Decodes just the header digits the way the interpreter used to, by way of
+/// a zero-padded string.
+fn string_header(value: i64) -> Option<(usize, usize, usize, usize)> {
+    let s = value.to_string();
+    if s.len() > 5 || value < 0 {
+        return None;
+    }
+    let padded = format!("{:0>5}", s);
+    let digits: Vec<usize> = padded
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .map(|d| d as usize)
+        .collect();
+    Some((digits[2], digits[1], digits[0], digits[3] * 10 + digits[4]))
+}
+
+/// `read_instruction`, but with the header split by `string_header`.
+fn string_instruction(offset: usize, data: &[i64]) -> Result<Op, IntcodeError> {
+    let value = data[offset];
+    let unknown = IntcodeError::UnknownOpcode {
+        address: offset,
+        opcode: value,
+    };
+    let (mode1, mode2, mode3, opcode) = string_header(value).ok_or_else(|| unknown.clone())?;
+    let param = |n: usize, mode: usize| {
+        let value = data.get(offset + n).copied().unwrap_or(0);
+        Param::from_pair(offset, (mode, value))
+    };
+    Ok(match opcode {
+        1 => Op::Add {
+            a: param(1, mode1)?,
+            b: param(2, mode2)?,
+            out: param(3, mode3)?,
+        },
+        2 => Op::Multiply {
+            a: param(1, mode1)?,
+            b: param(2, mode2)?,
+            out: param(3, mode3)?,
+        },
+        3 => Op::Input {
+            out: param(1, mode1)?,
+        },
+        4 => Op::Output {
+            value: param(1, mode1)?,
+        },
+        5 => Op::JumpIfTrue {
+            cond: param(1, mode1)?,
+            target: param(2, mode2)?,
+        },
+        6 => Op::JumpIfFalse {
+            cond: param(1, mode1)?,
+            target: param(2, mode2)?,
+        },
+        7 => Op::LessThan {
+            a: param(1, mode1)?,
+            b: param(2, mode2)?,
+            out: param(3, mode3)?,
+        },
+        8 => Op::Equals {
+            a: param(1, mode1)?,
+            b: param(2, mode2)?,
+            out: param(3, mode3)?,
+        },
+        9 => Op::AdjustRelativeBase {
+            value: param(1, mode1)?,
+        },
+        99 => Op::Halt,
+        _ => return Err(unknown),
+    })
+}
+
+/// A Day 2 style program, long enough for nouns and verbs up to 99 to point
+/// into it, which folds a chain of adds and multiplies over `[noun] + [verb]`.
+///
+/// If `linear`, the chain starts from `noun + verb` instead, like the real
+/// gravity assist program, so `solve` can skip straight to the answer.
+fn synthetic_day02(linear: bool) -> Vec<i64> {
+    let mut program = vec![1, 0, 0, 3];
+    if linear {
+        program.extend(&[1, 1, 2, 3]);
+    }
+    for i in 0..40 {
+        let opcode = if i % 3 == 0 { 2 } else { 1 };
+        program.extend(&[opcode, 3, 200 + i, 3]);
+    }
+    program.extend(&[1, 3, 0, 0, 99]);
+    program.extend((0..260 - program.len() as i64).map(|i| i % 7 + 1));
+    program
+}
+
+/// Counts down from `n` to zero in a tight loop, then halts.
+fn countdown(n: i64) -> Vec<i64> {
+    vec![1101, 0, n, 20, 1001, 20, -1, 20, 1005, 20, 4, 99]
+}
+
+fn main() {
+    let headers: Vec<i64> = (0..100_000)
+        .map(|i| [1, 2, 1002, 1101, 21201, 99][i % 6])
+        .collect();
+    let before = bench("decode/string", || {
+        (0..headers.len())
+            .filter(|&i| string_instruction(i, &headers).is_ok())
+            .count()
+    });
+    let after = bench("decode/arithmetic", || {
+        (0..headers.len())
+            .filter(|&i| read_instruction(i, &headers).is_ok())
+            .count()
+    });
+    speedup("decode/speedup", before, after);
+
+    let program = countdown(1_000_000);
+    let run = |cache: bool| {
+        let mut machine = Machine::new(program.clone());
+        machine.set_decode_cache(cache);
+        assert_eq!(machine.run(), Ok(Status::Halted));
+        machine.steps()
+    };
+    let before = bench("loop/uncached", || run(false));
+    let after = bench("loop/cached", || run(true));
+    speedup("loop/speedup", before, after);
+    let compiled = bench("loop/compiled", || {
+        compiled::compute_with_io(&mut program.clone(), &mut vec![], &mut vec![]).unwrap()
+    });
+    speedup("loop/compiled speedup", before, compiled);
+
+    let program = synthetic_day02(false);
+    let target = {
+        let mut memory = program.clone();
+        memory[1] = 98;
+        memory[2] = 99;
+        compute(&mut memory).unwrap();
+        memory[0]
+    };
+    // `solve` runs without the decode cache, since no pair runs long enough
+    // to decode an instruction twice.
+    let cached = Search::new().decode_cache(true);
+    let before = bench("solve/hit cached", || {
+        assert!(cached.run(target, &program).is_some())
+    });
+    let after = bench("solve/hit", || assert!(solve(target, &program).is_some()));
+    speedup("solve/hit speedup", before, after);
+    // Every cell is positive, so this searches all 10,000 pairs.
+    let before = bench("solve/miss cached", || {
+        assert_eq!(cached.run(-1, &program), None)
+    });
+    let after = bench("solve/miss", || assert_eq!(solve(-1, &program), None));
+    speedup("solve/miss speedup", before, after);
+
+    let program = synthetic_day02(true);
+    let target = {
+        let mut memory = program.clone();
+        memory[1] = 98;
+        memory[2] = 99;
+        compute(&mut memory).unwrap();
+        memory[0]
+    };
+    bench("solve/linear", || {
+        assert_eq!(solve(target, &program), Some((98, 99)))
+    });
+
+    let program: Vec<i64> = (0..1_000_000)
+        .map(|i| (i * 7919) % 20_001 - 10_000)
+        .collect();
+    let text = program
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    let mut bytes = vec![];
+    binary::save(&program, &mut bytes).unwrap();
+    println!(
+        "{:<24} {:>9} KiB text, {} KiB binary",
+        "load/size",
+        text.len() / 1024,
+        bytes.len() / 1024
+    );
+    let before = bench("load/text", || parse_program(&text).unwrap());
+    let after = bench("load/binary", || binary::from_bytes(&bytes).unwrap());
+    speedup("load/speedup", before, after);
+}
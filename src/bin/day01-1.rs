@@ -42,7 +42,7 @@ fn main() {
             _ => None,
         })
         .map(calc_required_fuel)
-        .fold(0, |acc, x| acc + x);
+        .sum::<i32>();
 
     println!("{}", total);
 }
@@ -44,7 +44,7 @@ fn main() {
             _ => None,
         })
         .map(calc_required_fuel_recursive)
-        .fold(0, |acc, x| acc + x);
+        .sum::<i32>();
 
     println!("{}", total);
 }
@@ -3,19 +3,22 @@ use aoc2019::intcode;
 use std::io::Read;
 
 fn main() {
-    let mut input: Vec<i32> = {
+    let mut input: Vec<i64> = {
         let mut buf = String::new();
         std::io::stdin().lock().read_to_string(&mut buf).unwrap();
-        buf.split(",")
-            .into_iter()
-            .filter_map(|s| s.trim().parse().ok())
-            .collect()
+        intcode::parse_program(&buf).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        })
     };
 
     // adding the magic smoke...
     input[1] = 12;
     input[2] = 2;
 
-    intcode::compute(&mut input);
+    if let Err(e) = intcode::compute(&mut input) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
     println!("{}", &input[0]);
 }
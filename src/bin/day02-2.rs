@@ -3,20 +3,20 @@ use aoc2019::intcode;
 use std::io::Read;
 
 fn main() {
-    let input: Vec<i32> = {
+    let input: Vec<i64> = {
         let mut buf = String::new();
         std::io::stdin().lock().read_to_string(&mut buf).unwrap();
-        buf.split(",")
-            .into_iter()
-            .filter_map(|s| s.trim().parse().ok())
-            .collect()
+        intcode::parse_program(&buf).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        })
     };
 
     match intcode::solve(19690720, &input) {
-        Ok((noun, verb)) => {
+        Some((noun, verb)) => {
             println!("noun={} verb={}", noun, verb);
             println!("{}", 100 * noun + verb);
         }
-        Err(_) => eprintln!("target not found"),
+        None => eprintln!("target not found"),
     }
 }
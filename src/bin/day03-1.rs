@@ -3,6 +3,9 @@ use aoc2019::crossed_wires;
 use std::io::BufRead;
 
 fn main() {
+    // Unreadable lines, such as ones which aren't UTF-8, are skipped rather
+    // than ending the input.
+    #[allow(clippy::lines_filter_map_ok)]
     let inputs: Vec<String> = {
         std::io::stdin()
             .lock()
@@ -11,7 +11,6 @@ fn main() {
         .unwrap();
 
     let count = (start..=end)
-        .into_iter()
         .filter(|&x| secure_container::is_more_valid(x))
         .count();
     println!("{}", count);
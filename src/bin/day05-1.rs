@@ -4,7 +4,7 @@ use std::fs::File;
 use std::io::Read;
 
 fn main() {
-    let mut input: Vec<i32> = {
+    let mut input: Vec<i64> = {
         // On the day 2 binaries we read the input from stdin, but for day 5 the
         // "input" instructions will prompt for user input, so this time we read the
         // intcode source in from file instead. There may be a way to handle both
@@ -18,11 +18,14 @@ fn main() {
         .unwrap();
         let mut buf = String::new();
         f.read_to_string(&mut buf).unwrap();
-        buf.split(",")
-            .into_iter()
-            .filter_map(|s| s.trim().parse().ok())
-            .collect()
+        intcode::parse_program(&buf).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        })
     };
 
-    intcode::compute(&mut input);
+    if let Err(e) = intcode::compute(&mut input) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,29 @@
+use aoc2019::intcode::asm;
+
+use std::fs::File;
+use std::io::Read;
+
+fn main() {
+    let source = {
+        let mut f = File::open(
+            std::env::args()
+                .nth(1)
+                .expect("must supply assembly source as file path"),
+        )
+        .unwrap();
+        let mut buf = String::new();
+        f.read_to_string(&mut buf).unwrap();
+        buf
+    };
+
+    match asm::assemble(&source) {
+        Ok(program) => {
+            let words: Vec<String> = program.iter().map(|x| x.to_string()).collect();
+            println!("{}", words.join(","));
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Prints the control-flow graph of an intcode program.
+//!
+//! Usage: `intcode-cfg <program> [--dot]`
+//!
+//! By default each basic block is listed with its successors, followed by any
+//! issues found. With `--dot` the graph is printed in Graphviz's DOT language
+//! instead, ready for `dot -Tsvg`.
+
+use aoc2019::intcode::cfg::{Cfg, Edge};
+use aoc2019::intcode::parse_program;
+
+use std::fs::File;
+use std::io::Read;
+
+fn main() {
+    let mut path = None;
+    let mut dot = false;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--dot" => dot = true,
+            _ => path = Some(arg),
+        }
+    }
+
+    let program: Vec<i64> = {
+        let mut f = File::open(path.expect("must supply intcode source as file path")).unwrap();
+        let mut buf = String::new();
+        f.read_to_string(&mut buf).unwrap();
+        parse_program(&buf).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        })
+    };
+
+    let cfg = Cfg::build(&program);
+    if dot {
+        print!("{}", cfg.to_dot());
+        return;
+    }
+
+    for block in cfg.blocks.values() {
+        println!("block {}:", block.start);
+        for (address, op) in &block.instructions {
+            println!("{:>5}: {}", address, op);
+        }
+        let mut successors: Vec<String> = block
+            .successors
+            .iter()
+            .map(|edge| match edge {
+                Edge::Fallthrough(target) => target.to_string(),
+                Edge::Jump(target) => format!("{} (jump)", target),
+            })
+            .collect();
+        if block.indirect {
+            successors.push("? (indirect)".to_string());
+        }
+        if successors.is_empty() {
+            println!("  -> halt");
+        } else {
+            println!("  -> {}", successors.join(", "));
+        }
+        println!();
+    }
+    for issue in &cfg.issues {
+        println!("warning: {}", issue);
+    }
+}
@@ -0,0 +1,62 @@
+//! Converts intcode programs between the text and binary formats.
+//!
+//! Usage: `intcode-convert <input> <output> [--to text|binary]`
+//!
+//! The input's format is detected from its contents. Without `--to`, a text
+//! program is converted to binary and a binary one to text.
+
+use aoc2019::intcode::{binary, parse_program};
+
+use std::fs::File;
+use std::io::{Read, Write};
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut paths = vec![];
+    let mut to_binary = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--to" => {
+                to_binary = match args.next().as_deref() {
+                    Some("binary") => Some(true),
+                    Some("text") => Some(false),
+                    _ => fail("--to needs `text` or `binary`"),
+                }
+            }
+            _ => paths.push(arg),
+        }
+    }
+    let (input, output) = match paths.as_slice() {
+        [input, output] => (input, output),
+        _ => fail("usage: intcode-convert <input> <output> [--to text|binary]"),
+    };
+
+    let mut bytes = vec![];
+    File::open(input)
+        .and_then(|mut f| f.read_to_end(&mut bytes))
+        .unwrap_or_else(|e| fail(e));
+    let is_binary = binary::is_binary_program(&bytes);
+    let program = if is_binary {
+        binary::from_bytes(&bytes).unwrap_or_else(|e| fail(e))
+    } else {
+        let text = String::from_utf8(bytes).unwrap_or_else(|_| fail("input is not text"));
+        parse_program(&text).unwrap_or_else(|e| fail(e))
+    };
+
+    let out = File::create(output).unwrap_or_else(|e| fail(e));
+    let result = if to_binary.unwrap_or(!is_binary) {
+        binary::save(&program, out)
+    } else {
+        let values: Vec<String> = program.iter().map(|v| v.to_string()).collect();
+        let mut out = out;
+        writeln!(out, "{}", values.join(","))
+    };
+    if let Err(e) = result {
+        fail(e);
+    }
+}
@@ -0,0 +1,50 @@
+use aoc2019::intcode::debugger::{Command, Debugger};
+use aoc2019::intcode::{parse_program, Machine};
+
+use std::fs::File;
+use std::io::{BufRead, Read, Write};
+
+fn main() {
+    let program: Vec<i64> = {
+        let mut f = File::open(
+            std::env::args()
+                .nth(1)
+                .expect("must supply intcode source as file path"),
+        )
+        .unwrap();
+        let mut buf = String::new();
+        f.read_to_string(&mut buf).unwrap();
+        parse_program(&buf).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        })
+    };
+
+    let mut debugger = Debugger::new(Machine::new(program));
+    println!(
+        "{}",
+        debugger.execute(Command::List {
+            address: None,
+            len: 1
+        })
+    );
+
+    let stdin = std::io::stdin();
+    loop {
+        print!("(icdb) ");
+        std::io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        match line.parse() {
+            Ok(Command::Quit) => break,
+            Ok(command) => println!("{}", debugger.execute(command)),
+            Err(e) => println!("{} (try `help`)", e),
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use aoc2019::intcode::{disasm, parse_program};
+
+use std::fs::File;
+use std::io::Read;
+
+fn main() {
+    let program: Vec<i64> = {
+        let mut f = File::open(
+            std::env::args()
+                .nth(1)
+                .expect("must supply intcode source as file path"),
+        )
+        .unwrap();
+        let mut buf = String::new();
+        f.read_to_string(&mut buf).unwrap();
+        parse_program(&buf).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        })
+    };
+
+    print!("{}", disasm::listing(&program));
+}
@@ -0,0 +1,138 @@
+//! Runs an intcode program against stdin and stdout.
+//!
+//! Usage: `intcode-run <program> [--trace <file>] [--record <file> | --replay <file>]
+//! [--profile] [--heatmap] [--self-modifying report|reject] [--max-steps <n>]
+//! [--max-memory <n>] [--max-outputs <n>]`
+//!
+//! The program may be in either the text format or the binary one written by
+//! `intcode-convert`.
+//!
+//! - `--trace` logs every executed instruction to `file` as a line of JSON
+//!   (see `aoc2019::intcode::trace`).
+//! - `--record` saves every input and output to a session file as it
+//!   happens, so the recording survives the program hanging or being killed.
+//! - `--replay` runs the program against a saved session instead of the
+//!   terminal, failing if the outputs differ from the recording.
+//! - `--profile` prints a report of the most executed opcodes, addresses and
+//!   loops to stderr once the program stops.
+//! - `--heatmap` prints the program's disassembly annotated with execution
+//!   counts to stderr once the program stops.
+//! - `--self-modifying report` lists writes into executed or upcoming code on
+//!   stderr once the program stops; `reject` stops the program at the first.
+//! - `--max-steps`, `--max-memory` and `--max-outputs` stop the program with
+//!   an error once it executes `n` instructions, writes past `n` memory cells
+//!   or outputs `n` values.
+
+use aoc2019::intcode::binary;
+use aoc2019::intcode::limits::Limits;
+use aoc2019::intcode::selfmod;
+use aoc2019::intcode::session::{self, Session, SessionWriter};
+use aoc2019::intcode::trace::Tracer;
+use aoc2019::intcode::{parse_program, Machine, StdinSource, StdoutSink};
+
+use std::fs::File;
+use std::io::Read;
+
+fn number<T: std::str::FromStr>(arg: Option<String>, flag: &str) -> T {
+    arg.and_then(|arg| arg.parse().ok())
+        .unwrap_or_else(|| panic!("{} needs a number", flag))
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut path = None;
+    let mut trace = None;
+    let mut record = None;
+    let mut replay = None;
+    let mut report = false;
+    let mut heatmap = false;
+    let mut self_modifying = None;
+    let mut limits = Limits::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--trace" => trace = Some(args.next().expect("--trace needs a file path")),
+            "--record" => record = Some(args.next().expect("--record needs a file path")),
+            "--replay" => replay = Some(args.next().expect("--replay needs a file path")),
+            "--profile" => report = true,
+            "--heatmap" => heatmap = true,
+            "--max-steps" => limits.steps = Some(number(args.next(), "--max-steps")),
+            "--max-memory" => limits.memory = Some(number(args.next(), "--max-memory")),
+            "--max-outputs" => limits.outputs = Some(number(args.next(), "--max-outputs")),
+            "--self-modifying" => {
+                self_modifying = match args.next().as_deref() {
+                    Some("report") => Some(selfmod::Mode::Report),
+                    Some("reject") => Some(selfmod::Mode::Reject),
+                    _ => panic!("--self-modifying needs `report` or `reject`"),
+                }
+            }
+            _ => path = Some(arg),
+        }
+    }
+
+    let program: Vec<i64> = {
+        let mut f = File::open(path.expect("must supply intcode source as file path")).unwrap();
+        let mut buf = vec![];
+        f.read_to_end(&mut buf).unwrap();
+        let program = if binary::is_binary_program(&buf) {
+            binary::from_bytes(&buf).map_err(|e| e.to_string())
+        } else {
+            parse_program(&String::from_utf8_lossy(&buf)).map_err(|e| e.to_string())
+        };
+        program.unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        })
+    };
+
+    let mut machine = Machine::new(program.clone());
+    machine.set_limits(limits);
+    if report || heatmap {
+        machine.enable_profiling();
+    }
+    if let Some(mode) = self_modifying {
+        machine.detect_self_modification(mode);
+    }
+    if let Some(trace) = trace {
+        machine.set_tracer(Tracer::create(trace).expect("trace file"));
+    }
+
+    let result = if let Some(replay) = replay {
+        let session = Session::load(File::open(replay).unwrap()).expect("session file");
+        session::replay(&mut machine, &session).map_err(|e| e.to_string())
+    } else if let Some(record) = record {
+        let mut writer = SessionWriter::create(record).expect("session file");
+        let result =
+            session::record_to(&mut machine, &mut StdinSource, &mut StdoutSink, &mut writer);
+        if let Err(e) = writer.finish() {
+            eprintln!("failed to write session: {}", e);
+        }
+        result.map_err(|e| e.to_string())
+    } else {
+        machine
+            .run_with_io(&mut StdinSource, &mut StdoutSink)
+            .map_err(|e| e.to_string())
+    };
+
+    if let Some(tracer) = machine.take_tracer() {
+        if let Err(e) = tracer.finish() {
+            eprintln!("failed to write trace: {}", e);
+        }
+    }
+    if let Some(profile) = machine.take_profile() {
+        if report {
+            eprintln!("{}", profile);
+        }
+        if heatmap {
+            eprintln!("{}", profile.heatmap(&program));
+        }
+    }
+    if self_modifying == Some(selfmod::Mode::Report) {
+        for write in machine.code_writes() {
+            eprintln!("{}", write);
+        }
+    }
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
@@ -127,7 +127,7 @@ impl FromStr for Step {
 
         let (head, tail) = chars.split_at(1);
         let distance = tail
-            .into_iter()
+            .iter()
             .collect::<String>()
             .parse::<i32>()
             .expect("dist parse");
@@ -146,7 +146,6 @@ pub fn calc_distance(a: &[Step], b: &[Step]) -> i32 {
     let b: HashSet<_> = plot_course(b).into_iter().collect();
 
     a.intersection(&b)
-        .into_iter()
         .map(|point| point.0.abs() + point.1.abs())
         .min()
         .expect("min distance")
@@ -175,7 +174,6 @@ pub fn calc_fewest_steps(a: &[Step], b: &[Step]) -> Option<usize> {
 
     a_set
         .intersection(&b_set)
-        .into_iter()
         .map(|point| a_map[point] + b_map[point])
         .min()
 }
@@ -185,11 +183,11 @@ fn plot_course(directions: &[Step]) -> Vec<Point> {
     for step in directions {
         let &(x, y) = buf.iter().last().unwrap_or(&(0, 0));
 
-        match step {
-            &Step::Left(dist) => buf.extend((1..=dist as usize).map(|i| (x - i as i32, y))),
-            &Step::Right(dist) => buf.extend((1..=dist as usize).map(|i| (x + i as i32, y))),
-            &Step::Up(dist) => buf.extend((1..=dist as usize).map(|i| (x, y + i as i32))),
-            &Step::Down(dist) => buf.extend((1..=dist as usize).map(|i| (x, y - i as i32))),
+        match *step {
+            Step::Left(dist) => buf.extend((1..=dist as usize).map(|i| (x - i as i32, y))),
+            Step::Right(dist) => buf.extend((1..=dist as usize).map(|i| (x + i as i32, y))),
+            Step::Up(dist) => buf.extend((1..=dist as usize).map(|i| (x, y + i as i32))),
+            Step::Down(dist) => buf.extend((1..=dist as usize).map(|i| (x, y - i as i32))),
         };
     }
     buf
@@ -247,9 +247,256 @@
 //!
 //! After providing 1 to the only input instruction and passing all the tests,
 //! what diagnostic code does the program produce?
+//!
+//! ## Part 2
+//!
+//! The air conditioner comes online! Its cold air feels good for a while, but
+//! then the TEST alarms start to go off. Since the air conditioner can't vent
+//! its heat anywhere but back into the spacecraft, it's actually making the air
+//! inside the ship warmer.
+//!
+//! Instead, you'll need to use the TEST to extend the thermal radiators.
+//! Fortunately, the diagnostic program (your puzzle input) is already equipped
+//! for this. Unfortunately, your Intcode computer is not.
+//!
+//! Your computer is only missing a few opcodes:
+//!
+//! - Opcode `5` is **jump-if-true**: if the first parameter is non-zero, it sets
+//!   the instruction pointer to the value from the second parameter. Otherwise,
+//!   it does nothing.
+//! - Opcode `6` is **jump-if-false**: if the first parameter is zero, it sets the
+//!   instruction pointer to the value from the second parameter. Otherwise, it
+//!   does nothing.
+//! - Opcode `7` is **less than**: if the first parameter is less than the second
+//!   parameter, it stores `1` in the position given by the third parameter.
+//!   Otherwise, it stores `0`.
+//! - Opcode `8` is **equals**: if the first parameter is equal to the second
+//!   parameter, it stores `1` in the position given by the third parameter.
+//!   Otherwise, it stores `0`.
+//!
+//! Like all instructions, these instructions need to support parameter modes as
+//! described above.
+//!
+//! Normally, after an instruction is finished, the instruction pointer increases
+//! by the number of values in that instruction. However, if the instruction
+//! modifies the instruction pointer, that value is used and the instruction
+//! pointer is **not automatically increased**.
+//!
+//! For example, here are several programs that take one input, compare it to the
+//! value `8`, and then produce one output:
+//!
+//! - `3,9,8,9,10,9,4,9,99,-1,8` - Using position mode, consider whether the input
+//!   is equal to `8`; output `1` (if it is) or `0` (if it is not).
+//! - `3,9,7,9,10,9,4,9,99,-1,8` - Using position mode, consider whether the input
+//!   is less than `8`; output `1` (if it is) or `0` (if it is not).
+//! - `3,3,1108,-1,8,3,4,3,99` - Using immediate mode, consider whether the input
+//!   is equal to `8`; output `1` (if it is) or `0` (if it is not).
+//! - `3,3,1107,-1,8,3,4,3,99` - Using immediate mode, consider whether the input
+//!   is less than `8`; output `1` (if it is) or `0` (if it is not).
+//!
+//! Here are some jump tests that take an input, then output `0` if the input was
+//! zero or `1` if the input was non-zero:
+//!
+//! - `3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9` (using position mode)
+//! - `3,3,1105,-1,9,1101,0,0,12,4,12,99,1` (using immediate mode)
+//!
+//! Here's a larger example:
+//!
+//! ```text
+//! 3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,
+//! 1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,
+//! 999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99
+//! ```
+//!
+//! The above example program uses an input instruction to ask for a single
+//! number. The program will then output `999` if the input value is below `8`,
+//! output `1000` if the input value is equal to `8`, or output `1001` if the
+//! input value is greater than `8`.
+//!
+//! This time, when the TEST diagnostic program runs its input instruction to get
+//! the ID of the system to test, provide it `5`, the ID for the ship's thermal
+//! radiator controller. This diagnostic test suite only outputs one number, the
+//! **diagnostic code**.
+//!
+//! What is the diagnostic code for system ID `5`?
+//!
+//! # Day 9: Sensor Boost
+//!
+//! ## Part 1
+//!
+//! You've just said goodbye to the rebooted rover and left Mars when you
+//! receive a faint distress signal coming from the asteroid belt. It seems to be
+//! the Ceres monitoring station!
+//!
+//! In order to lock on to the signal, you'll need to boost your sensors. The
+//! Elves send up the latest **BOOST** program - Basic Operation Of System Test.
+//!
+//! While BOOST (your puzzle input) is capable of boosting your sensors, for
+//! tenuous safety reasons, it refuses to do so until the computer it runs on
+//! passes some checks to demonstrate it is a **complete Intcode computer**.
+//!
+//! Your existing Intcode computer is missing one key feature: it needs support
+//! for parameters in **relative mode**.
+//!
+//! Parameters in mode `2`, relative mode, behave very similarly to parameters in
+//! position mode: the parameter is interpreted as a position. Like position
+//! mode, parameters in relative mode can be read from or written to.
+//!
+//! The important difference is that relative mode parameters don't count from
+//! address `0`. Instead, they count from a value called the **relative base**.
+//! The relative base starts at `0`.
+//!
+//! The address a relative mode parameter refers to is itself **plus** the
+//! current relative base. When the relative base is `0`, relative mode
+//! parameters and position mode parameters with the same value refer to the
+//! same address.
+//!
+//! For example, given a relative base of `50`, a relative mode parameter of `-7`
+//! refers to memory address `50 + -7 = 43`.
+//!
+//! The relative base is modified with the **relative base offset** instruction:
+//!
+//! - Opcode `9` **adjusts the relative base** by the value of its only
+//!   parameter. The relative base increases (or decreases, if the value is
+//!   negative) by the value of the parameter.
+//!
+//! For example, if the relative base is `2000`, then after the instruction
+//! `109,19`, the relative base would be `2019`. If the next instruction were
+//! `204,-34`, then the value at address `1985` would be output.
+//!
+//! Your Intcode computer will also need a few other capabilities:
+//!
+//! - The computer's available memory should be much larger than the initial
+//!   program. Memory beyond the initial program starts with the value `0` and
+//!   can be read or written like any other memory. (It is invalid to try to
+//!   access memory at a negative address, though.)
+//! - The computer should have support for large numbers. Some instructions near
+//!   the beginning of the BOOST program will verify this capability.
+//!
+//! Here are some example programs that use these features:
+//!
+//! - `109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99` takes no input
+//!   and produces a copy of itself as output.
+//! - `1102,34915192,34915192,7,4,7,99,0` should output a 16-digit number.
+//! - `104,1125899906842624,99` should output the large number in the middle.
+
+pub mod asm;
+pub mod binary;
+mod cache;
+pub mod cfg;
+pub mod compiled;
+pub mod debugger;
+pub mod disasm;
+pub mod history;
+pub mod limits;
+mod parse;
+pub mod profile;
+pub mod search;
+pub mod selfmod;
+pub mod session;
+pub mod snapshot;
+pub mod symbolic;
+pub mod trace;
+pub mod watch;
 
+use cache::DecodeCache;
+use history::History;
+use limits::Limits;
+pub use parse::{parse_program, parse_program_with, ParseError, ParseOptions};
+use profile::Profile;
+use selfmod::CodeGuard;
+use std::collections::VecDeque;
 use std::convert::TryFrom;
+use std::fmt;
+use std::io::BufRead;
 use std::str::FromStr;
+use trace::TraceSlot;
+use watch::{WatchHit, Watchpoint};
+
+/// Everything that can go wrong while running an intcode program.
+///
+/// Each variant carries the address of the instruction that failed so the
+/// caller can point at the offending spot in the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntcodeError {
+    /// The value at `address` does not decode to a known instruction.
+    UnknownOpcode { address: usize, opcode: i64 },
+    /// A parameter of the instruction at `address` has a mode other than 0, 1 or 2.
+    InvalidMode { address: usize, mode: usize },
+    /// The instruction at `address` tried to write through an immediate param.
+    WriteToImmediate { address: usize },
+    /// The instruction at `address` referred to a memory location below zero.
+    NegativeAddress { address: usize, target: i64 },
+    /// Arithmetic in the instruction at `address` overflowed 64 bits.
+    Overflow { address: usize },
+    /// The instruction at `address` asked for input but none was available.
+    InputExhausted { address: usize },
+    /// The instruction at `address` tried to write into code at `target`,
+    /// with self-modification rejected.
+    SelfModifying { address: usize, target: usize },
+    /// The program ran for `limit` steps without halting.
+    StepLimit { limit: u64 },
+    /// The instruction at `address` tried to write to `target`, beyond the
+    /// memory limit of `limit` cells.
+    MemoryLimit {
+        address: usize,
+        target: usize,
+        limit: usize,
+    },
+    /// The output instruction at `address` would exceed `limit` outputs.
+    OutputLimit { address: usize, limit: usize },
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntcodeError::UnknownOpcode { address, opcode } => {
+                write!(f, "unknown opcode {} at address {}", opcode, address)
+            }
+            IntcodeError::InvalidMode { address, mode } => {
+                write!(f, "invalid parameter mode {} at address {}", mode, address)
+            }
+            IntcodeError::WriteToImmediate { address } => {
+                write!(f, "write to immediate param at address {}", address)
+            }
+            IntcodeError::NegativeAddress { address, target } => write!(
+                f,
+                "negative memory address {} referenced at address {}",
+                target, address
+            ),
+            IntcodeError::Overflow { address } => {
+                write!(f, "arithmetic overflow at address {}", address)
+            }
+            IntcodeError::InputExhausted { address } => {
+                write!(f, "input exhausted at address {}", address)
+            }
+            IntcodeError::SelfModifying { address, target } => write!(
+                f,
+                "write to code at {} by instruction at address {}",
+                target, address
+            ),
+            IntcodeError::StepLimit { limit } => {
+                write!(f, "program did not halt within {} steps", limit)
+            }
+            IntcodeError::MemoryLimit {
+                address,
+                target,
+                limit,
+            } => write!(
+                f,
+                "write to {} at address {} exceeds the memory limit of {} cells",
+                target, address, limit
+            ),
+            IntcodeError::OutputLimit { address, limit } => write!(
+                f,
+                "output at address {} exceeds the limit of {} outputs",
+                address, limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IntcodeError {}
 
 #[derive(Debug, PartialEq)]
 struct OpHeader {
@@ -259,193 +506,763 @@ struct OpHeader {
     opcode: usize,
 }
 
+/// Produced when a value is too large or negative to be a header.
+#[derive(Debug, PartialEq)]
+struct InvalidHeader;
+
 impl FromStr for OpHeader {
-    type Err = ();
+    type Err = InvalidHeader;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // initial string should not be larger than 5 or smaller than 1 chars.
-        if s.len() > 5 || s.len() < 1 {
-            return Err(());
-        }
-
-        let padded = format!("{:0>5}", s.chars().take(5).collect::<String>());
-        let (modes, opcode) = padded.split_at(3);
-
-        let modes: Vec<u32> = modes.chars().filter_map(|c| c.to_digit(10)).collect();
-        let opcode: usize = opcode.parse().map_err(|_| ())?;
-
-        Ok(OpHeader {
-            mode1: modes[2] as usize,
-            mode2: modes[1] as usize,
-            mode3: modes[0] as usize,
-            opcode,
-        })
+        let value: i64 = s.parse().map_err(|_| InvalidHeader)?;
+        OpHeader::try_from(value)
     }
 }
 
-impl TryFrom<i32> for OpHeader {
-    type Error = ();
+impl TryFrom<i64> for OpHeader {
+    type Error = InvalidHeader;
 
-    fn try_from(value: i32) -> Result<Self, Self::Error> {
-        value.to_string().parse()
+    /// Splits a header into its digits arithmetically, since this runs for
+    /// every executed instruction.
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
+        if !(0..100_000).contains(&value) {
+            return Err(InvalidHeader);
+        }
+        let value = value as usize;
+        Ok(OpHeader {
+            mode1: value / 100 % 10,
+            mode2: value / 1_000 % 10,
+            mode3: value / 10_000,
+            opcode: value % 100,
+        })
     }
 }
 
-#[derive(Debug)]
-enum Param {
-    Immediate(i32),
+/// A decoded instruction parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Param {
+    Immediate(i64),
     Position(usize),
+    Relative(i64),
 }
 
 impl Param {
-    pub fn from_pair((mode, value): (usize, i32)) -> Self {
+    /// Builds a param for the instruction at `address` from a mode and raw value.
+    pub fn from_pair(address: usize, (mode, value): (usize, i64)) -> Result<Self, IntcodeError> {
         match mode {
-            0 => Param::Position(value as usize),
-            1 => Param::Immediate(value),
-            _ => unreachable!(),
+            0 => usize::try_from(value).map(Param::Position).map_err(|_| {
+                IntcodeError::NegativeAddress {
+                    address,
+                    target: value,
+                }
+            }),
+            1 => Ok(Param::Immediate(value)),
+            2 => Ok(Param::Relative(value)),
+            _ => Err(IntcodeError::InvalidMode { address, mode }),
         }
     }
 }
 
-#[derive(Debug)]
-enum Op {
-    Add { a: Param, b: Param, out: usize },
-    Multiply { a: Param, b: Param, out: usize },
-    Input { out: usize },
+/// A decoded instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Add { a: Param, b: Param, out: Param },
+    Multiply { a: Param, b: Param, out: Param },
+    Input { out: Param },
     Output { value: Param },
+    JumpIfTrue { cond: Param, target: Param },
+    JumpIfFalse { cond: Param, target: Param },
+    LessThan { a: Param, b: Param, out: Param },
+    Equals { a: Param, b: Param, out: Param },
+    AdjustRelativeBase { value: Param },
     Halt,
-    Unknown,
+}
+
+impl Op {
+    /// The number of values the instruction occupies in memory, which is also
+    /// how far the instruction pointer moves when it doesn't jump.
+    pub fn width(&self) -> usize {
+        match self {
+            Op::Add { .. } | Op::Multiply { .. } | Op::LessThan { .. } | Op::Equals { .. } => 4,
+            Op::JumpIfTrue { .. } | Op::JumpIfFalse { .. } => 3,
+            Op::Input { .. } | Op::Output { .. } | Op::AdjustRelativeBase { .. } => 2,
+            Op::Halt => 1,
+        }
+    }
 }
 
 /// Builds an `Op` from `data` by reading up to 4 items from a given offset.
-fn read_instruction(offset: usize, data: &[i32]) -> Op {
-    // FIXME: add support for Input/Output
-    let header: Option<OpHeader> = data.get(offset).and_then(|x| OpHeader::try_from(*x).ok());
-    match (
-        header,
-        data.get(offset + 1).map(|x| *x),
-        data.get(offset + 2).map(|x| *x),
-        data.get(offset + 3).map(|x| *x),
-    ) {
-        (
-            Some(OpHeader {
-                opcode: 1,
-                mode1,
-                mode2,
-                mode3,
-            }),
-            Some(a),
-            Some(b),
-            Some(out),
-        ) => Op::Add {
-            a: Param::from_pair((mode1, a)),
-            b: Param::from_pair((mode2, b)),
-            out: match Param::from_pair((mode3, out)) {
-                Param::Position(out) => out,
-                _ => unreachable!("output params cannot be immediate"),
-            },
+pub fn read_instruction(offset: usize, data: &[i64]) -> Result<Op, IntcodeError> {
+    let value = load(data, offset);
+    let unknown = IntcodeError::UnknownOpcode {
+        address: offset,
+        opcode: value,
+    };
+    let OpHeader {
+        mode1,
+        mode2,
+        mode3,
+        opcode,
+    } = OpHeader::try_from(value).map_err(|_| unknown.clone())?;
+    let param = |n: usize, mode: usize| Param::from_pair(offset, (mode, load(data, offset + n)));
+
+    Ok(match opcode {
+        1 => Op::Add {
+            a: param(1, mode1)?,
+            b: param(2, mode2)?,
+            out: param(3, mode3)?,
         },
-        (
-            Some(OpHeader {
-                opcode: 2,
-                mode1,
-                mode2,
-                mode3,
-            }),
-            Some(a),
-            Some(b),
-            Some(out),
-        ) => Op::Multiply {
-            a: Param::from_pair((mode1, a)),
-            b: Param::from_pair((mode2, b)),
-            out: match Param::from_pair((mode3, out)) {
-                Param::Position(out) => out,
-                _ => unreachable!("output params cannot be immediate"),
-            },
+        2 => Op::Multiply {
+            a: param(1, mode1)?,
+            b: param(2, mode2)?,
+            out: param(3, mode3)?,
         },
-        (Some(OpHeader { opcode: 3, .. }), Some(out), _, _) => Op::Input { out: out as usize },
-        (
-            Some(OpHeader {
-                opcode: 4, mode1, ..
-            }),
-            Some(value),
-            _,
-            _,
-        ) => Op::Output {
-            value: Param::from_pair((mode1, value)),
+        3 => Op::Input {
+            out: param(1, mode1)?,
+        },
+        4 => Op::Output {
+            value: param(1, mode1)?,
+        },
+        5 => Op::JumpIfTrue {
+            cond: param(1, mode1)?,
+            target: param(2, mode2)?,
+        },
+        6 => Op::JumpIfFalse {
+            cond: param(1, mode1)?,
+            target: param(2, mode2)?,
+        },
+        7 => Op::LessThan {
+            a: param(1, mode1)?,
+            b: param(2, mode2)?,
+            out: param(3, mode3)?,
         },
-        (Some(OpHeader { opcode: 99, .. }), _, _, _) => Op::Halt,
-        _ => Op::Unknown,
+        8 => Op::Equals {
+            a: param(1, mode1)?,
+            b: param(2, mode2)?,
+            out: param(3, mode3)?,
+        },
+        9 => Op::AdjustRelativeBase {
+            value: param(1, mode1)?,
+        },
+        99 => Op::Halt,
+        _ => return Err(unknown),
+    })
+}
+
+/// Decodes the instructions in a program one after another, starting from
+/// address 0.
+///
+/// Each item is the address and the result of decoding there. Decoding moves
+/// past a failed instruction one value at a time, so data mixed in with code
+/// shows up as a run of errors.
+pub struct Instructions<'a> {
+    data: &'a [i64],
+    offset: usize,
+}
+
+impl<'a> Instructions<'a> {
+    pub fn new(data: &'a [i64]) -> Self {
+        Instructions { data, offset: 0 }
     }
 }
 
-fn read_value(param: Param, data: &[i32]) -> Option<i32> {
-    match param {
-        Param::Position(idx) => data.get(idx).map(|x| *x),
-        Param::Immediate(val) => Some(val),
+impl<'a> Iterator for Instructions<'a> {
+    type Item = (usize, Result<Op, IntcodeError>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.data.len() {
+            return None;
+        }
+        let address = self.offset;
+        let op = read_instruction(address, self.data);
+        self.offset += op.as_ref().map(Op::width).unwrap_or(1);
+        Some((address, op))
     }
 }
 
-use std::io::BufRead;
-fn prompt_for_input() -> Result<i32, ()> {
-    let mut buf = String::new();
-    println!("Waiting for input... >");
-    std::io::stdin()
-        .lock()
-        .read_line(&mut buf)
-        .expect("input read");
-    buf.trim().parse().map_err(|e| {
-        eprintln!("{}", e);
+/// Reads the value at `address`, treating anything past the end of `data` as
+/// zeroed memory.
+fn load(data: &[i64], address: usize) -> i64 {
+    data.get(address).copied().unwrap_or(0)
+}
+
+/// Writes `value` to `address`, growing `data` with zeroes as needed.
+fn store(data: &mut Vec<i64>, address: usize, value: i64) {
+    if address >= data.len() {
+        data.resize(address + 1, 0);
+    }
+    data[address] = value;
+}
+
+/// Resolves a relative param against the current base, rejecting anything
+/// which lands below address zero.
+fn relative_address(ip: usize, offset: i64, relative_base: i64) -> Result<usize, IntcodeError> {
+    let target = relative_base
+        .checked_add(offset)
+        .ok_or(IntcodeError::Overflow { address: ip })?;
+    usize::try_from(target).map_err(|_| IntcodeError::NegativeAddress {
+        address: ip,
+        target,
     })
 }
 
-/// Run an intcode program.
-pub fn compute(data: &mut [i32]) {
-    let mut i = 0;
-    loop {
-        // FIXME: make read_instruction an iterator so it can manage the increment internally
-        match read_instruction(i, &data) {
+/// Resolves the memory address a param refers to. Immediate params have no
+/// address, so are rejected as the target of a write.
+fn param_address(ip: usize, param: Param, relative_base: i64) -> Result<usize, IntcodeError> {
+    match param {
+        Param::Position(idx) => Ok(idx),
+        Param::Relative(offset) => relative_address(ip, offset, relative_base),
+        Param::Immediate(_) => Err(IntcodeError::WriteToImmediate { address: ip }),
+    }
+}
+
+/// Somewhere for a running program to pull its input values from.
+pub trait InputSource {
+    /// Produces the next input value, or `None` if there are none left.
+    fn next_input(&mut self) -> Option<i64>;
+}
+
+/// Somewhere for a running program to send its output values.
+pub trait OutputSink {
+    fn send_output(&mut self, value: i64);
+}
+
+/// Values are consumed from the front of the queue.
+impl InputSource for VecDeque<i64> {
+    fn next_input(&mut self) -> Option<i64> {
+        self.pop_front()
+    }
+}
+
+/// Values are consumed from the front of the vec.
+impl InputSource for Vec<i64> {
+    fn next_input(&mut self) -> Option<i64> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.remove(0))
+        }
+    }
+}
+
+/// Feeds input from any iterator of values, such as a range or
+/// `std::iter::from_fn` wrapping a closure.
+pub struct IterSource<I>(pub I);
+
+impl<I: Iterator<Item = i64>> InputSource for IterSource<I> {
+    fn next_input(&mut self) -> Option<i64> {
+        self.0.next()
+    }
+}
+
+/// Prompts for each input value on stdout and reads it from a line of stdin.
+///
+/// Lines which don't parse as a number are reported and the prompt repeats.
+/// Input is exhausted once stdin is closed.
+pub struct StdinSource;
+
+impl InputSource for StdinSource {
+    fn next_input(&mut self) -> Option<i64> {
+        let stdin = std::io::stdin();
+        loop {
+            let mut buf = String::new();
+            println!("Waiting for input... >");
+            let read = stdin.lock().read_line(&mut buf).expect("input read");
+            if read == 0 {
+                return None;
+            }
+            match buf.trim().parse() {
+                Ok(value) => return Some(value),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+    }
+}
+
+impl OutputSink for Vec<i64> {
+    fn send_output(&mut self, value: i64) {
+        self.push(value);
+    }
+}
+
+impl OutputSink for VecDeque<i64> {
+    fn send_output(&mut self, value: i64) {
+        self.push_back(value);
+    }
+}
+
+/// Prints each output value on its own line.
+pub struct StdoutSink;
+
+impl OutputSink for StdoutSink {
+    fn send_output(&mut self, value: i64) {
+        println!("{}", value);
+    }
+}
+
+/// The reason `Machine::run` handed control back to the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// The program reached a halt instruction. Running again has no effect.
+    Halted,
+    /// The program wants to read input but the input queue is empty. Push a
+    /// value with `Machine::push_input` and run again to resume.
+    NeedsInput,
+    /// The program produced a value. Run again to resume.
+    Output(i64),
+    /// An instruction touched a watched memory cell. Run again to resume.
+    Watchpoint(WatchHit),
+}
+
+/// A memory write performed by a single instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryWrite {
+    pub address: usize,
+    /// The value held at `address` before the write.
+    pub old: i64,
+    pub new: i64,
+}
+
+/// A record of everything that happened while executing one instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    /// Address of the instruction that was executed.
+    pub address: usize,
+    pub op: Op,
+    /// Memory cells read while resolving params, as `(address, value)`.
+    pub reads: Vec<(usize, i64)>,
+    pub writes: Vec<MemoryWrite>,
+    /// Instruction pointer after the step.
+    pub ip: usize,
+    /// Relative base after the step.
+    pub relative_base: i64,
+    /// Set when the step needs the caller's attention. A `NeedsInput` step did
+    /// not execute and will be retried once input is available. Watchpoints are
+    /// reported separately in `watch_hits`.
+    pub status: Option<Status>,
+    pub watch_hits: Vec<WatchHit>,
+}
+
+/// An intcode computer which can be paused and resumed.
+///
+/// The machine owns its memory, instruction pointer and relative base, so a
+/// program can be run in bursts interleaved with logic on the host side.
+#[derive(Debug, Clone)]
+pub struct Machine {
+    memory: Vec<i64>,
+    ip: usize,
+    relative_base: i64,
+    input: VecDeque<i64>,
+    /// Every value output so far which hasn't been taken.
+    output: Vec<i64>,
+    watchpoints: Vec<Watchpoint>,
+    /// Statuses held back so a step's first watchpoint hit can be reported
+    /// first: any further hits, then the step's own status.
+    pending: VecDeque<Status>,
+    /// The number of instructions executed so far.
+    steps: u64,
+    tracer: TraceSlot,
+    history: Option<History>,
+    profile: Option<Profile>,
+    cache: DecodeCache,
+    code_guard: Option<CodeGuard>,
+    limits: Limits,
+    /// How many values have been output, whether or not they've been taken.
+    outputs: usize,
+    /// Whether output goes into `output`. It doesn't while a sink is taking
+    /// it, so a long run doesn't hold on to every value ever output.
+    buffer_output: bool,
+    /// Whether the halt instruction at `ip` has already been executed, so
+    /// running again doesn't count, trace or record it a second time.
+    halted: bool,
+    /// Whether the step being executed fills in its reads and writes. They're
+    /// left out while running with nothing to look at them, which saves
+    /// allocating for every instruction.
+    detailed: bool,
+}
+
+impl Machine {
+    pub fn new(program: Vec<i64>) -> Self {
+        Machine {
+            memory: program,
+            ip: 0,
+            relative_base: 0,
+            input: VecDeque::new(),
+            output: vec![],
+            watchpoints: vec![],
+            pending: VecDeque::new(),
+            steps: 0,
+            tracer: TraceSlot::default(),
+            history: None,
+            profile: None,
+            cache: DecodeCache::default(),
+            code_guard: None,
+            limits: Limits::default(),
+            outputs: 0,
+            buffer_output: true,
+            halted: false,
+            detailed: true,
+        }
+    }
+
+    /// Queues a value to be consumed by the next input instruction.
+    pub fn push_input(&mut self, value: i64) {
+        self.input.push_back(value);
+    }
+
+    pub fn memory(&self) -> &[i64] {
+        &self.memory
+    }
+
+    pub fn into_memory(self) -> Vec<i64> {
+        self.memory
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    /// The number of instructions executed so far.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Values queued for upcoming input instructions.
+    pub fn pending_input(&self) -> &VecDeque<i64> {
+        &self.input
+    }
+
+    /// Values output so far, oldest first. These are also handed out one at a
+    /// time by `run`, so callers can use whichever is more convenient. Output
+    /// passed to a sink, as by `run_with_io`, isn't kept here.
+    pub fn output(&self) -> &[i64] {
+        &self.output
+    }
+
+    /// Empties the output buffer, returning its contents.
+    pub fn take_output(&mut self) -> Vec<i64> {
+        std::mem::take(&mut self.output)
+    }
+
+    /// Reads a single memory cell. Cells past the end of memory read as 0.
+    pub fn peek(&self, address: usize) -> i64 {
+        load(&self.memory, address)
+    }
+
+    /// Overwrites a single memory cell, growing memory if needed.
+    pub fn poke(&mut self, address: usize, value: i64) {
+        store(&mut self.memory, address, value);
+        self.cache.invalidate(address);
+        if address == self.ip {
+            self.halted = false;
+        }
+    }
+
+    /// Turns the decoded-instruction cache on or off. It's on by default; this
+    /// is mostly useful for measuring it, or ruling it out when debugging.
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.cache = if enabled {
+            DecodeCache::default()
+        } else {
+            DecodeCache::disabled()
+        };
+    }
+
+    /// Runs until the program halts, needs input, produces output, or
+    /// triggers a watchpoint. A step which triggers several watchpoints
+    /// reports each of them from successive calls, before anything else.
+    pub fn run(&mut self) -> Result<Status, IntcodeError> {
+        if let Some(status) = self.pending.pop_front() {
+            return Ok(status);
+        }
+        loop {
+            let detailed =
+                !self.watchpoints.is_empty() || self.tracer.is_active() || self.history.is_some();
+            let step = self.step_with(detailed)?;
+            let mut hits = step.watch_hits.into_iter();
+            if let Some(hit) = hits.next() {
+                self.pending.extend(hits.map(Status::Watchpoint));
+                self.pending.extend(step.status);
+                return Ok(Status::Watchpoint(hit));
+            }
+            if let Some(status) = step.status {
+                return Ok(status);
+            }
+        }
+    }
+
+    /// Runs until the program halts, pulling input from `input` whenever it's
+    /// needed and passing output along to `output`. Watchpoints are ignored.
+    pub fn run_with_io(
+        &mut self,
+        input: &mut impl InputSource,
+        output: &mut impl OutputSink,
+    ) -> Result<(), IntcodeError> {
+        self.unbuffered(|machine| loop {
+            match machine.run()? {
+                Status::Halted => return Ok(()),
+                Status::NeedsInput => match input.next_input() {
+                    Some(value) => machine.push_input(value),
+                    None => {
+                        return Err(IntcodeError::InputExhausted {
+                            address: machine.ip,
+                        })
+                    }
+                },
+                Status::Output(value) => output.send_output(value),
+                Status::Watchpoint(_) => {}
+            }
+        })
+    }
+
+    /// Runs `f` with output left out of the output buffer, for callers which
+    /// pass every output straight on to somewhere else.
+    pub(super) fn unbuffered<T>(&mut self, f: impl FnOnce(&mut Machine) -> T) -> T {
+        let buffer_output = std::mem::replace(&mut self.buffer_output, false);
+        let result = f(self);
+        self.buffer_output = buffer_output;
+        result
+    }
+
+    fn read(&self, step: &mut Step, param: Param) -> Result<i64, IntcodeError> {
+        if let Param::Immediate(value) = param {
+            return Ok(value);
+        }
+        let address = param_address(self.ip, param, self.relative_base)?;
+        let value = load(&self.memory, address);
+        if self.detailed {
+            step.reads.push((address, value));
+        }
+        Ok(value)
+    }
+
+    fn write(&mut self, step: &mut Step, param: Param, value: i64) -> Result<(), IntcodeError> {
+        let address = param_address(self.ip, param, self.relative_base)?;
+        if let Some(limit) = self.limits.memory {
+            if address >= limit {
+                return Err(IntcodeError::MemoryLimit {
+                    address: self.ip,
+                    target: address,
+                    limit,
+                });
+            }
+        }
+        if let Some(guard) = self.code_guard.as_mut() {
+            let next = self.ip + step.op.width();
+            let hit = guard.check(self.ip, next, address, &self.memory);
+            if hit.is_some() && guard.mode == selfmod::Mode::Reject {
+                return Err(IntcodeError::SelfModifying {
+                    address: self.ip,
+                    target: address,
+                });
+            }
+        }
+        if self.detailed {
+            step.writes.push(MemoryWrite {
+                address,
+                old: load(&self.memory, address),
+                new: value,
+            });
+        }
+        store(&mut self.memory, address, value);
+        self.cache.invalidate(address);
+        Ok(())
+    }
+
+    /// Executes exactly one instruction and reports what it did.
+    pub fn step(&mut self) -> Result<Step, IntcodeError> {
+        self.step_with(true)
+    }
+
+    /// Executes one instruction, leaving the step's reads and writes empty
+    /// unless `detailed`.
+    fn step_with(&mut self, detailed: bool) -> Result<Step, IntcodeError> {
+        self.detailed = detailed;
+        // Halting again isn't a new step, so it's allowed even at the limit.
+        if let (Some(limit), false) = (self.limits.steps, self.halted) {
+            if self.steps >= limit {
+                return Err(IntcodeError::StepLimit { limit });
+            }
+        }
+        let (ip, relative_base, memory_len) = (self.ip, self.relative_base, self.memory.len());
+        let step = match self.execute() {
+            Ok(step) => step,
+            Err(e) => {
+                if self.tracer.is_active() {
+                    let op = read_instruction(ip, &self.memory).ok();
+                    self.tracer.record_error(self.steps, ip, op, &e);
+                }
+                return Err(e);
+            }
+        };
+        let halted = step.status == Some(Status::Halted);
+        if std::mem::replace(&mut self.halted, halted) && halted {
+            return Ok(step);
+        }
+        if step.status != Some(Status::NeedsInput) {
+            self.tracer.record(self.steps, &step);
+            let guarded = self
+                .code_guard
+                .as_mut()
+                .map(|guard| guard.mark(step.address, step.op.width()));
+            if let Some(history) = self.history.as_mut() {
+                let buffered = self.buffer_output;
+                history.push(ip, relative_base, memory_len, buffered, guarded, &step);
+            }
+            if let Some(profile) = self.profile.as_mut() {
+                profile.record(&step);
+            }
+            self.steps += 1;
+        }
+        Ok(step)
+    }
+
+    fn execute(&mut self) -> Result<Step, IntcodeError> {
+        let op = self.cache.decode(self.ip, &self.memory)?;
+        let mut step = Step {
+            address: self.ip,
+            op,
+            reads: vec![],
+            writes: vec![],
+            ip: self.ip,
+            relative_base: self.relative_base,
+            status: None,
+            watch_hits: vec![],
+        };
+        let mut next = self.ip + op.width();
+
+        match op {
             Op::Add { a, b, out } => {
-                let a = read_value(a, data).unwrap();
-                let b = read_value(b, data).unwrap();
-                data[out] = a + b;
-                i += 4;
+                let value = self
+                    .read(&mut step, a)?
+                    .checked_add(self.read(&mut step, b)?);
+                let value = value.ok_or(IntcodeError::Overflow { address: self.ip })?;
+                self.write(&mut step, out, value)?;
             }
             Op::Multiply { a, b, out } => {
-                let a = read_value(a, data).unwrap();
-                let b = read_value(b, data).unwrap();
-                data[out] = a * b;
-                i += 4;
-            }
-            Op::Input { out } => {
-                let value = prompt_for_input().unwrap();
-                data[out] = value;
-                i += 2;
+                let value = self
+                    .read(&mut step, a)?
+                    .checked_mul(self.read(&mut step, b)?);
+                let value = value.ok_or(IntcodeError::Overflow { address: self.ip })?;
+                self.write(&mut step, out, value)?;
             }
+            Op::Input { out } => match self.input.pop_front() {
+                Some(value) => self.write(&mut step, out, value)?,
+                None => {
+                    step.status = Some(Status::NeedsInput);
+                    return Ok(step);
+                }
+            },
             Op::Output { value } => {
-                let value = read_value(value, data).unwrap();
-                println!("offset={}, value={}", i, value);
-                i += 2;
+                let value = self.read(&mut step, value)?;
+                if let Some(limit) = self.limits.outputs {
+                    if self.outputs >= limit {
+                        return Err(IntcodeError::OutputLimit {
+                            address: self.ip,
+                            limit,
+                        });
+                    }
+                }
+                self.outputs += 1;
+                if self.buffer_output {
+                    self.output.push(value);
+                }
+                step.status = Some(Status::Output(value));
+            }
+            Op::JumpIfTrue { cond, target } => {
+                if self.read(&mut step, cond)? != 0 {
+                    next = jump_target(self.ip, self.read(&mut step, target)?)?;
+                }
+            }
+            Op::JumpIfFalse { cond, target } => {
+                if self.read(&mut step, cond)? == 0 {
+                    next = jump_target(self.ip, self.read(&mut step, target)?)?;
+                }
+            }
+            Op::LessThan { a, b, out } => {
+                let value = if self.read(&mut step, a)? < self.read(&mut step, b)? {
+                    1
+                } else {
+                    0
+                };
+                self.write(&mut step, out, value)?;
+            }
+            Op::Equals { a, b, out } => {
+                let value = if self.read(&mut step, a)? == self.read(&mut step, b)? {
+                    1
+                } else {
+                    0
+                };
+                self.write(&mut step, out, value)?;
+            }
+            Op::AdjustRelativeBase { value } => {
+                let base = self.relative_base.checked_add(self.read(&mut step, value)?);
+                self.relative_base = base.ok_or(IntcodeError::Overflow { address: self.ip })?;
+            }
+            Op::Halt => {
+                step.status = Some(Status::Halted);
+                return Ok(step);
             }
-            Op::Halt => break,
-            _ => unreachable!(),
         }
+
+        self.ip = next;
+        step.ip = self.ip;
+        step.relative_base = self.relative_base;
+        step.watch_hits = self.check_watchpoints(&step);
+        Ok(step)
     }
 }
 
+/// Run an intcode program, prompting on stdin for input and printing output.
+pub fn compute(data: &mut Vec<i64>) -> Result<(), IntcodeError> {
+    compute_with_io(data, &mut StdinSource, &mut StdoutSink)
+}
+
+/// Run an intcode program, reading input from `input` and sending output to
+/// `output`.
+pub fn compute_with_io(
+    data: &mut Vec<i64>,
+    input: &mut impl InputSource,
+    output: &mut impl OutputSink,
+) -> Result<(), IntcodeError> {
+    compute_with_limits(data, input, output, Limits::default())
+}
+
+/// Run an intcode program like `compute_with_io`, failing if it goes past
+/// any of `limits`.
+pub fn compute_with_limits(
+    data: &mut Vec<i64>,
+    input: &mut impl InputSource,
+    output: &mut impl OutputSink,
+    limits: Limits,
+) -> Result<(), IntcodeError> {
+    let mut machine = Machine::new(std::mem::take(data));
+    machine.set_limits(limits);
+    let result = machine.run_with_io(input, output);
+    *data = machine.into_memory();
+    result
+}
+
+/// Validates the destination of a jump taken by the instruction at `ip`.
+fn jump_target(ip: usize, target: i64) -> Result<usize, IntcodeError> {
+    usize::try_from(target).map_err(|_| IntcodeError::NegativeAddress {
+        address: ip,
+        target,
+    })
+}
+
 /// Attempt to identify the noun and verb (injected header values) which will
 /// yield a certain target from a source intcode program by way of permutations.
-pub fn solve(target: i32, data: &[i32]) -> Result<(i32, i32), ()> {
-    for (noun, verb) in (0..=99).flat_map(|i| (0..=99).map(move |j| (i, j))) {
-        let mut input = data.to_vec();
-        input[1] = noun;
-        input[2] = verb;
-        compute(&mut input);
-        if input[0] == target {
-            return Ok((noun, verb));
-        }
-    }
-    Err(())
+///
+/// Pairs which cause the program to fail, or which run past generous limits on
+/// steps, memory and output, are skipped. See `search::Search` to
+/// change the ranges searched, the address checked, or the number of threads.
+pub fn solve(target: i64, data: &[i64]) -> Option<(i64, i64)> {
+    search::Search::new().run(target, data)
 }
 
 #[cfg(test)]
@@ -455,28 +1272,28 @@ mod day02_1_tests {
     #[test]
     fn test_example_1() {
         let mut input = vec![1, 0, 0, 0, 99];
-        compute(&mut input);
+        compute(&mut input).unwrap();
         assert_eq!(&input, &[2, 0, 0, 0, 99]);
     }
 
     #[test]
     fn test_example_2() {
         let mut input = vec![2, 3, 0, 3, 99];
-        compute(&mut input);
+        compute(&mut input).unwrap();
         assert_eq!(&input, &[2, 3, 0, 6, 99]);
     }
 
     #[test]
     fn test_example_3() {
         let mut input = vec![2, 4, 4, 5, 99, 0];
-        compute(&mut input);
+        compute(&mut input).unwrap();
         assert_eq!(&input, &[2, 4, 4, 5, 99, 9801]);
     }
 
     #[test]
     fn test_example_4() {
         let mut input = vec![1, 1, 1, 4, 99, 5, 6, 0, 99];
-        compute(&mut input);
+        compute(&mut input).unwrap();
         assert_eq!(&input, &[30, 1, 1, 4, 2, 5, 6, 0, 99]);
     }
 }
@@ -484,6 +1301,7 @@ mod day02_1_tests {
 #[cfg(test)]
 mod day05_1_tests {
     use super::{compute, OpHeader};
+    use std::convert::TryFrom;
 
     #[test]
     fn test_header_pad() {
@@ -512,10 +1330,439 @@ mod day05_1_tests {
         );
     }
 
+    #[test]
+    fn test_header_out_of_range() {
+        assert!(OpHeader::try_from(-1).is_err());
+        assert!(OpHeader::try_from(100_000).is_err());
+        assert_eq!(OpHeader::try_from(21_199).unwrap().mode3, 2);
+    }
+
     #[test]
     fn test_example_1() {
         let mut input = vec![1002, 4, 3, 4, 33];
-        compute(&mut input);
+        compute(&mut input).unwrap();
         assert_eq!(&input, &[1002, 4, 3, 4, 99]);
     }
 }
+
+#[cfg(test)]
+mod day05_2_tests {
+    use super::{compute, compute_with_io};
+
+    fn run(program: &[i64], input: i64) -> Vec<i64> {
+        let mut output = vec![];
+        compute_with_io(&mut program.to_vec(), &mut vec![input], &mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_less_than_immediate() {
+        let mut input = vec![1107, 1, 2, 5, 99, -1];
+        compute(&mut input).unwrap();
+        assert_eq!(&input, &[1107, 1, 2, 5, 99, 1]);
+    }
+
+    #[test]
+    fn test_equals_position() {
+        let mut input = vec![8, 5, 6, 7, 99, 8, 9, -1];
+        compute(&mut input).unwrap();
+        assert_eq!(&input, &[8, 5, 6, 7, 99, 8, 9, 0]);
+    }
+
+    #[test]
+    fn test_jump_if_true_taken() {
+        // jumps over the early halt to an add that writes to position 0
+        let mut input = vec![1105, 1, 4, 99, 1101, 2, 3, 0, 99];
+        compute(&mut input).unwrap();
+        assert_eq!(&input, &[5, 1, 4, 99, 1101, 2, 3, 0, 99]);
+    }
+
+    #[test]
+    fn test_jump_if_false_not_taken() {
+        // a non-zero condition falls through to the halt at position 3
+        let mut input = vec![1106, 1, 4, 99, 1101, 2, 3, 0, 99];
+        compute(&mut input).unwrap();
+        assert_eq!(&input, &[1106, 1, 4, 99, 1101, 2, 3, 0, 99]);
+    }
+
+    #[test]
+    fn test_equal_to_8_position_mode() {
+        let program = [3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+        assert_eq!(run(&program, 8), &[1]);
+        assert_eq!(run(&program, 7), &[0]);
+    }
+
+    #[test]
+    fn test_less_than_8_position_mode() {
+        let program = [3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8];
+        assert_eq!(run(&program, 7), &[1]);
+        assert_eq!(run(&program, 8), &[0]);
+    }
+
+    #[test]
+    fn test_equal_to_8_immediate_mode() {
+        let program = [3, 3, 1108, -1, 8, 3, 4, 3, 99];
+        assert_eq!(run(&program, 8), &[1]);
+        assert_eq!(run(&program, 9), &[0]);
+    }
+
+    #[test]
+    fn test_less_than_8_immediate_mode() {
+        let program = [3, 3, 1107, -1, 8, 3, 4, 3, 99];
+        assert_eq!(run(&program, -3), &[1]);
+        assert_eq!(run(&program, 10), &[0]);
+    }
+
+    #[test]
+    fn test_jumps_position_mode() {
+        let program = [3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+        assert_eq!(run(&program, 0), &[0]);
+        assert_eq!(run(&program, 5), &[1]);
+    }
+
+    #[test]
+    fn test_jumps_immediate_mode() {
+        let program = [3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
+        assert_eq!(run(&program, 0), &[0]);
+        assert_eq!(run(&program, 5), &[1]);
+    }
+
+    #[test]
+    fn test_larger_example() {
+        let program = [
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99,
+        ];
+        assert_eq!(run(&program, 7), &[999]);
+        assert_eq!(run(&program, 8), &[1000]);
+        assert_eq!(run(&program, 9), &[1001]);
+    }
+}
+
+#[cfg(test)]
+mod day09_1_tests {
+    use super::{compute, compute_with_io};
+
+    #[test]
+    fn test_relative_write() {
+        // the relative base moves to 5, so the add writes to position 5
+        let mut input = vec![109, 5, 21101, 2, 3, 0, 99];
+        compute(&mut input).unwrap();
+        assert_eq!(&input, &[109, 5, 21101, 2, 3, 5, 99]);
+    }
+
+    #[test]
+    fn test_relative_read() {
+        let mut input = vec![109, 7, 2201, 0, 1, 0, 99, 10, 20];
+        compute(&mut input).unwrap();
+        assert_eq!(&input, &[30, 7, 2201, 0, 1, 0, 99, 10, 20]);
+    }
+
+    #[test]
+    fn test_large_numbers() {
+        let mut input = vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0];
+        compute(&mut input).unwrap();
+        assert_eq!(input[7], 1219070632396864);
+    }
+
+    #[test]
+    fn test_write_past_end_grows_memory() {
+        let mut input = vec![1101, 1, 2, 10, 99];
+        compute(&mut input).unwrap();
+        assert_eq!(&input, &[1101, 1, 2, 10, 99, 0, 0, 0, 0, 0, 3]);
+    }
+
+    #[test]
+    fn test_read_past_end_is_zero() {
+        let mut input = vec![1001, 100, 7, 0, 99];
+        compute(&mut input).unwrap();
+        assert_eq!(&input, &[7, 100, 7, 0, 99]);
+    }
+
+    #[test]
+    fn test_negative_adjustment() {
+        // the second adjustment moves the base back from 10 to 4
+        let mut input = vec![109, 10, 109, -6, 21101, 7, 8, 0, 99];
+        compute(&mut input).unwrap();
+        assert_eq!(&input, &[109, 10, 109, -6, 15, 7, 8, 0, 99]);
+    }
+
+    #[test]
+    fn test_quine() {
+        let program = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        let mut output = vec![];
+        compute_with_io(&mut program.clone(), &mut vec![], &mut output).unwrap();
+        assert_eq!(output, program);
+    }
+
+    #[test]
+    fn test_large_output() {
+        let mut output = vec![];
+        compute_with_io(
+            &mut vec![104, 1125899906842624, 99],
+            &mut vec![],
+            &mut output,
+        )
+        .unwrap();
+        assert_eq!(output, &[1125899906842624]);
+    }
+}
+
+#[cfg(test)]
+mod error_tests {
+    use super::{compute, IntcodeError};
+
+    #[test]
+    fn test_unknown_opcode() {
+        let mut input = vec![1101, 1, 1, 5, 42, 0];
+        assert_eq!(
+            compute(&mut input),
+            Err(IntcodeError::UnknownOpcode {
+                address: 4,
+                opcode: 42
+            })
+        );
+    }
+
+    #[test]
+    fn test_overflow() {
+        let overflow = |program: &[i64]| compute(&mut program.to_vec());
+        assert_eq!(
+            overflow(&[1101, i64::MAX, 1, 0, 99]),
+            Err(IntcodeError::Overflow { address: 0 })
+        );
+        assert_eq!(
+            overflow(&[1, 0, 0, 0, 1102, i64::MIN, -1, 0, 99]),
+            Err(IntcodeError::Overflow { address: 4 })
+        );
+        // Both adjusting the relative base and resolving a relative param.
+        assert_eq!(
+            overflow(&[109, i64::MAX, 109, 1, 99]),
+            Err(IntcodeError::Overflow { address: 2 })
+        );
+        assert_eq!(
+            overflow(&[109, i64::MAX, 204, 1, 99]),
+            Err(IntcodeError::Overflow { address: 2 })
+        );
+    }
+
+    #[test]
+    fn test_invalid_mode() {
+        let mut input = vec![301, 0, 0, 0, 99];
+        assert_eq!(
+            compute(&mut input),
+            Err(IntcodeError::InvalidMode {
+                address: 0,
+                mode: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_write_to_immediate() {
+        let mut input = vec![11101, 1, 1, 0, 99];
+        assert_eq!(
+            compute(&mut input),
+            Err(IntcodeError::WriteToImmediate { address: 0 })
+        );
+    }
+
+    #[test]
+    fn test_negative_address() {
+        let mut input = vec![1, -1, 0, 0, 99];
+        assert_eq!(
+            compute(&mut input),
+            Err(IntcodeError::NegativeAddress {
+                address: 0,
+                target: -1
+            })
+        );
+    }
+
+    #[test]
+    fn test_negative_relative_address() {
+        let mut input = vec![109, -5, 204, 0, 99];
+        assert_eq!(
+            compute(&mut input),
+            Err(IntcodeError::NegativeAddress {
+                address: 2,
+                target: -5
+            })
+        );
+    }
+}
+
+#[cfg(test)]
+mod io_tests {
+    use super::{compute_with_io, IntcodeError, IterSource, Machine, Status};
+    use std::collections::VecDeque;
+
+    #[test]
+    fn test_sink_output_not_buffered() {
+        let mut machine = Machine::new(vec![104, 1, 104, 2, 99]);
+        let mut output = vec![];
+        machine.run_with_io(&mut vec![], &mut output).unwrap();
+        assert_eq!(output, vec![1, 2]);
+        assert!(machine.output().is_empty());
+
+        let mut machine = Machine::new(vec![104, 1, 104, 2, 99]);
+        assert_eq!(machine.run(), Ok(Status::Output(1)));
+        assert_eq!(machine.output(), &[1]);
+    }
+
+    #[test]
+    fn test_queue_input_and_output() {
+        // echoes two inputs back out
+        let mut input: VecDeque<i64> = vec![4, 5].into();
+        let mut output = VecDeque::new();
+        compute_with_io(
+            &mut vec![3, 0, 4, 0, 3, 0, 4, 0, 99],
+            &mut input,
+            &mut output,
+        )
+        .unwrap();
+        assert!(input.is_empty());
+        assert_eq!(output, vec![4, 5]);
+    }
+
+    #[test]
+    fn test_iter_input() {
+        let mut output = vec![];
+        compute_with_io(
+            &mut vec![3, 0, 4, 0, 3, 0, 4, 0, 99],
+            &mut IterSource(10..),
+            &mut output,
+        )
+        .unwrap();
+        assert_eq!(output, &[10, 11]);
+    }
+
+    #[test]
+    fn test_input_exhausted() {
+        let mut output = vec![];
+        assert_eq!(
+            compute_with_io(&mut vec![3, 0, 4, 0, 3, 0, 99], &mut vec![1], &mut output),
+            Err(IntcodeError::InputExhausted { address: 4 })
+        );
+        assert_eq!(output, &[1]);
+    }
+}
+
+#[cfg(test)]
+mod machine_tests {
+    use super::{Machine, Status};
+
+    #[test]
+    fn test_pauses_for_input_and_output() {
+        let mut machine = Machine::new(vec![3, 0, 4, 0, 99]);
+        assert_eq!(machine.run(), Ok(Status::NeedsInput));
+        // asking again without supplying input is harmless
+        assert_eq!(machine.run(), Ok(Status::NeedsInput));
+        machine.push_input(42);
+        assert_eq!(machine.run(), Ok(Status::Output(42)));
+        assert_eq!(machine.run(), Ok(Status::Halted));
+        assert_eq!(machine.run(), Ok(Status::Halted));
+        // halting again doesn't count as another step
+        assert_eq!(machine.steps(), 3);
+        assert_eq!(machine.memory(), &[42, 0, 4, 0, 99]);
+        assert_eq!(machine.take_output(), &[42]);
+        assert!(machine.output().is_empty());
+    }
+
+    /// The amplifier feedback loop from day 7: each machine's output is fed
+    /// into the next until the last amplifier halts.
+    #[test]
+    fn test_feedback_loop() {
+        let program = vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ];
+        let mut amps: Vec<Machine> = [9, 8, 7, 6, 5]
+            .iter()
+            .map(|&phase| {
+                let mut amp = Machine::new(program.clone());
+                amp.push_input(phase);
+                amp
+            })
+            .collect();
+
+        let mut signal = 0;
+        'outer: loop {
+            for amp in amps.iter_mut() {
+                amp.push_input(signal);
+                match amp.run().unwrap() {
+                    Status::Output(value) => signal = value,
+                    Status::Halted => break 'outer,
+                    Status::NeedsInput | Status::Watchpoint(_) => unreachable!(),
+                }
+            }
+        }
+        assert_eq!(signal, 139629729);
+    }
+}
+
+#[cfg(test)]
+mod step_tests {
+    use super::{Instructions, Machine, MemoryWrite, Op, Param, Status};
+
+    #[test]
+    fn test_step_reports_reads_and_writes() {
+        let mut machine = Machine::new(vec![1, 5, 6, 0, 99, 7, 8]);
+        let step = machine.step().unwrap();
+        assert_eq!(step.address, 0);
+        assert_eq!(
+            step.op,
+            Op::Add {
+                a: Param::Position(5),
+                b: Param::Position(6),
+                out: Param::Position(0),
+            }
+        );
+        assert_eq!(step.reads, &[(5, 7), (6, 8)]);
+        assert_eq!(
+            step.writes,
+            &[MemoryWrite {
+                address: 0,
+                old: 1,
+                new: 15
+            }]
+        );
+        assert_eq!(step.ip, 4);
+        assert_eq!(step.status, None);
+
+        let step = machine.step().unwrap();
+        assert_eq!(step.op, Op::Halt);
+        assert_eq!(step.status, Some(Status::Halted));
+        assert_eq!(machine.ip(), 4);
+    }
+
+    #[test]
+    fn test_step_jump() {
+        let mut machine = Machine::new(vec![1105, 1, 7, 99, 99, 99, 99, 99]);
+        let step = machine.step().unwrap();
+        assert!(step.reads.is_empty());
+        assert_eq!(step.ip, 7);
+        assert_eq!(machine.ip(), 7);
+    }
+
+    #[test]
+    fn test_step_blocked_on_input() {
+        let mut machine = Machine::new(vec![3, 0, 99]);
+        let step = machine.step().unwrap();
+        assert_eq!(step.status, Some(Status::NeedsInput));
+        assert_eq!(step.ip, 0);
+        assert!(step.writes.is_empty());
+    }
+
+    #[test]
+    fn test_instructions() {
+        let program = [1002, 4, 3, 4, 33, 99];
+        let decoded: Vec<_> = Instructions::new(&program)
+            .map(|(address, op)| (address, op.is_ok()))
+            .collect();
+        assert_eq!(decoded, &[(0, true), (4, false), (5, true)]);
+    }
+}
@@ -0,0 +1,461 @@
+//! Builds intcode programs from a small assembly language.
+//!
+//! The syntax is the same one `disasm` produces, so a listing can be edited
+//! and fed straight back in:
+//!
+//! ```text
+//! ; echo input until a zero is read
+//! loop: in [buf]
+//!       jf [buf], #end
+//!       out [buf]
+//!       jt #1, #loop
+//! end:  hlt
+//! buf:  .data 0
+//! ```
+//!
+//! - Operands are written `[n]` for position mode, `#n` for immediate mode and
+//!   `rb+n` (or `rb-n`) for relative mode.
+//! - Anywhere a number is expected, a label may be used instead, optionally
+//!   with an offset such as `[buf+1]`. That includes relative offsets, where
+//!   `rb-buf` subtracts the label's address.
+//! - `.data` emits its comma separated values as-is.
+//! - Everything after a `;` is a comment.
+//! - A leading address like `12:` (as found in a listing) is ignored.
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// Reasons a source file can fail to assemble. Line numbers start at 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmError {
+    UnknownMnemonic {
+        line: usize,
+        mnemonic: String,
+    },
+    OperandCount {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    InvalidOperand {
+        line: usize,
+        operand: String,
+    },
+    InvalidLabel {
+        line: usize,
+        label: String,
+    },
+    DuplicateLabel {
+        line: usize,
+        label: String,
+    },
+    UnknownLabel {
+        line: usize,
+        label: String,
+    },
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsmError::UnknownMnemonic { line, mnemonic } => {
+                write!(f, "line {}: unknown mnemonic `{}`", line, mnemonic)
+            }
+            AsmError::OperandCount {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: expected {} operands, found {}",
+                line, expected, found
+            ),
+            AsmError::InvalidOperand { line, operand } => {
+                write!(f, "line {}: invalid operand `{}`", line, operand)
+            }
+            AsmError::InvalidLabel { line, label } => {
+                write!(f, "line {}: invalid label name `{}`", line, label)
+            }
+            AsmError::DuplicateLabel { line, label } => {
+                write!(f, "line {}: label `{}` is already defined", line, label)
+            }
+            AsmError::UnknownLabel { line, label } => {
+                write!(f, "line {}: unknown label `{}`", line, label)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// A number, or a label plus an offset to be resolved once every label's
+/// address is known.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Literal(i64),
+    Label(String, i64),
+    /// The label's address subtracted from the offset, as in `rb-label`.
+    NegatedLabel(String, i64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Position(Value),
+    Immediate(Value),
+    Relative(Value),
+}
+
+/// One parsed line's worth of output, before labels are resolved.
+#[derive(Debug)]
+enum Item {
+    Instruction {
+        line: usize,
+        opcode: i64,
+        operands: Vec<Operand>,
+    },
+    Data {
+        line: usize,
+        values: Vec<Value>,
+    },
+}
+
+/// Looks up the opcode and operand count for a mnemonic.
+fn opcode_for(mnemonic: &str) -> Option<(i64, usize)> {
+    Some(match mnemonic {
+        "add" => (1, 3),
+        "mul" => (2, 3),
+        "in" => (3, 1),
+        "out" => (4, 1),
+        "jt" => (5, 2),
+        "jf" => (6, 2),
+        "lt" => (7, 3),
+        "eq" => (8, 3),
+        "arb" => (9, 1),
+        "hlt" => (99, 0),
+        _ => return None,
+    })
+}
+
+fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    s != "rb" && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_value(line: usize, s: &str) -> Result<Value, AsmError> {
+    let s = s.trim();
+    let invalid = || AsmError::InvalidOperand {
+        line,
+        operand: s.to_string(),
+    };
+    if let Ok(n) = s.parse() {
+        return Ok(Value::Literal(n));
+    }
+    // look for an offset after the label, skipping the first character so a
+    // leading sign isn't mistaken for one.
+    let (label, offset) = match s
+        .char_indices()
+        .skip(1)
+        .find(|&(_, c)| c == '+' || c == '-')
+    {
+        Some((i, _)) => {
+            let (label, offset) = s.split_at(i);
+            let offset = offset.trim_start_matches('+').trim();
+            (label.trim(), offset.parse().map_err(|_| invalid())?)
+        }
+        None => (s, 0),
+    };
+    if is_label(label) {
+        Ok(Value::Label(label.to_string(), offset))
+    } else {
+        Err(invalid())
+    }
+}
+
+fn parse_operand(line: usize, s: &str) -> Result<Operand, AsmError> {
+    let s = s.trim();
+    if s.starts_with('[') && s.ends_with(']') {
+        Ok(Operand::Position(parse_value(line, &s[1..s.len() - 1])?))
+    } else if let Some(rest) = s.strip_prefix('#') {
+        Ok(Operand::Immediate(parse_value(line, rest)?))
+    } else if s == "rb" {
+        Ok(Operand::Relative(Value::Literal(0)))
+    } else if let Some(rest) = s.strip_prefix("rb+") {
+        Ok(Operand::Relative(parse_value(line, rest)?))
+    } else if let Some(rest) = s.strip_prefix("rb-") {
+        let value = match parse_value(line, rest)? {
+            Value::Literal(n) => {
+                Value::Literal(n.checked_neg().ok_or_else(|| AsmError::InvalidOperand {
+                    line,
+                    operand: s.to_string(),
+                })?)
+            }
+            Value::Label(label, offset) => Value::NegatedLabel(label, offset),
+            Value::NegatedLabel(label, offset) => Value::Label(label, offset),
+        };
+        Ok(Operand::Relative(value))
+    } else {
+        Err(AsmError::InvalidOperand {
+            line,
+            operand: s.to_string(),
+        })
+    }
+}
+
+/// Splits a comma separated list, treating an empty string as an empty list.
+fn split_list(s: &str) -> Vec<&str> {
+    if s.trim().is_empty() {
+        vec![]
+    } else {
+        s.split(',').collect()
+    }
+}
+
+/// Assembles `source` into a program ready to be loaded by `compute` or a
+/// `Machine`.
+pub fn assemble(source: &str) -> Result<Vec<i64>, AsmError> {
+    let mut labels: HashMap<String, usize> = HashMap::new();
+    let mut items = vec![];
+    let mut address = 0;
+
+    for (i, text) in source.lines().enumerate() {
+        let line = i + 1;
+        let mut rest = text.split(';').next().unwrap_or("").trim();
+
+        // peel off any labels (or listing addresses) ahead of the statement
+        while let Some(colon) = rest.find(':') {
+            let name = rest[..colon].trim();
+            if name.chars().all(|c| c.is_ascii_digit()) && !name.is_empty() {
+                // an address from a listing
+            } else if is_label(name) {
+                if labels.insert(name.to_string(), address).is_some() {
+                    return Err(AsmError::DuplicateLabel {
+                        line,
+                        label: name.to_string(),
+                    });
+                }
+            } else {
+                return Err(AsmError::InvalidLabel {
+                    line,
+                    label: name.to_string(),
+                });
+            }
+            rest = rest[colon + 1..].trim();
+        }
+
+        if rest.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, args) = match rest.find(char::is_whitespace) {
+            Some(i) => (&rest[..i], rest[i..].trim()),
+            None => (rest, ""),
+        };
+
+        if mnemonic == ".data" {
+            let values = split_list(args)
+                .into_iter()
+                .map(|s| parse_value(line, s))
+                .collect::<Result<Vec<_>, _>>()?;
+            address += values.len();
+            items.push(Item::Data { line, values });
+            continue;
+        }
+
+        let (opcode, arity) = opcode_for(mnemonic).ok_or_else(|| AsmError::UnknownMnemonic {
+            line,
+            mnemonic: mnemonic.to_string(),
+        })?;
+        let operands = split_list(args)
+            .into_iter()
+            .map(|s| parse_operand(line, s))
+            .collect::<Result<Vec<_>, _>>()?;
+        if operands.len() != arity {
+            return Err(AsmError::OperandCount {
+                line,
+                expected: arity,
+                found: operands.len(),
+            });
+        }
+        address += 1 + arity;
+        items.push(Item::Instruction {
+            line,
+            opcode,
+            operands,
+        });
+    }
+
+    let resolve = |line: usize, value: &Value| -> Result<i64, AsmError> {
+        let (label, offset, sign) = match value {
+            Value::Literal(n) => return Ok(*n),
+            Value::Label(label, offset) => (label, offset, 1),
+            Value::NegatedLabel(label, offset) => (label, offset, -1),
+        };
+        let address = *labels.get(label).ok_or_else(|| AsmError::UnknownLabel {
+            line,
+            label: label.clone(),
+        })?;
+        (address as i64)
+            .checked_mul(sign)
+            .and_then(|address| address.checked_add(*offset))
+            .ok_or_else(|| AsmError::InvalidOperand {
+                line,
+                operand: format!("{}{}{:+}", if sign < 0 { "-" } else { "" }, label, offset),
+            })
+    };
+
+    let mut program = Vec::with_capacity(address);
+    for item in &items {
+        match item {
+            Item::Instruction {
+                line,
+                opcode,
+                operands,
+            } => {
+                let mut header = *opcode;
+                let mut scale = 100;
+                let mut params = vec![];
+                for operand in operands {
+                    let (mode, value) = match operand {
+                        Operand::Position(value) => (0, value),
+                        Operand::Immediate(value) => (1, value),
+                        Operand::Relative(value) => (2, value),
+                    };
+                    header += mode * scale;
+                    scale *= 10;
+                    params.push(resolve(*line, value)?);
+                }
+                program.push(header);
+                program.extend(params);
+            }
+            Item::Data { line, values } => {
+                for value in values {
+                    program.push(resolve(*line, value)?);
+                }
+            }
+        }
+    }
+    Ok(program)
+}
+
+#[cfg(test)]
+mod asm_tests {
+    use super::{assemble, AsmError};
+    use crate::intcode::{compute_with_io, disasm::listing};
+
+    #[test]
+    fn test_labels_and_data() {
+        let source = "
+            ; echo input until a zero is read
+            loop: in [buf]
+                  jf [buf], #end
+                  out [buf]
+                  jt #1, #loop
+            end:  hlt
+            buf:  .data 0
+        ";
+        let program = assemble(source).unwrap();
+        assert_eq!(program, &[3, 11, 1006, 11, 10, 4, 11, 1105, 1, 0, 99, 0]);
+
+        let mut output = vec![];
+        compute_with_io(&mut program.clone(), &mut vec![4, 5, 0], &mut output).unwrap();
+        assert_eq!(output, &[4, 5]);
+    }
+
+    #[test]
+    fn test_modes_and_offsets() {
+        assert_eq!(
+            assemble("add #5, rb-2, rb+3\nmul [x+1], rb, [x]\nx: .data 7, 8").unwrap(),
+            &[22101, 5, -2, 3, 2002, 9, 0, 8, 7, 8]
+        );
+        assert_eq!(
+            assemble(
+                "arb #x
+out rb-x
+out rb+x
+out rb-x+1
+hlt
+x: .data 7"
+            )
+            .unwrap(),
+            &[109, 9, 204, -9, 204, 9, 204, -8, 99, 7]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let programs: &[&[i64]] = &[
+            &[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50],
+            &[
+                109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+            ],
+            &[
+                3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36,
+                98, 0, 0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000,
+                1, 20, 4, 20, 1105, 1, 46, 98, 99,
+            ],
+            // headers with unused or leading mode digits
+            &[1104, 5, 199, 10105, 1, 0, 21101, 1, 2, 3, 99],
+        ];
+        for program in programs {
+            assert_eq!(&assemble(&listing(program)).unwrap(), program);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            assemble("hlt\nnop"),
+            Err(AsmError::UnknownMnemonic {
+                line: 2,
+                mnemonic: "nop".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("add #1, #2"),
+            Err(AsmError::OperandCount {
+                line: 1,
+                expected: 3,
+                found: 2
+            })
+        );
+        assert_eq!(
+            assemble("out 5"),
+            Err(AsmError::InvalidOperand {
+                line: 1,
+                operand: "5".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("jt #1, #nowhere"),
+            Err(AsmError::UnknownLabel {
+                line: 1,
+                label: "nowhere".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("add [x+9223372036854775807], #1, [x]\nx: hlt"),
+            Err(AsmError::InvalidOperand {
+                line: 1,
+                operand: "x+9223372036854775807".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("hlt\nout rb-x-9223372036854775808\nx: hlt"),
+            Err(AsmError::InvalidOperand {
+                line: 2,
+                operand: "-x-9223372036854775808".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("a: hlt\na: hlt"),
+            Err(AsmError::DuplicateLabel {
+                line: 2,
+                label: "a".to_string()
+            })
+        );
+    }
+}
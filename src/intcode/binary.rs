@@ -0,0 +1,436 @@
+//! A compact binary format for programs and snapshots.
+//!
+//! Large generated programs are slow to read as text and take up far more
+//! room than they need to, since most values are small. A binary file is laid
+//! out as:
+//!
+//! - a 4 byte magic number, `\x7fICP` for a program or `\x7fICS` for a
+//!   snapshot;
+//! - a format version byte, currently 2;
+//! - a word size byte from 1 to 8. Each signed value is limited to that many
+//!   bytes of two's complement, and a wider one is an error. This crate always
+//!   writes 8;
+//! - the contents, made of unsigned LEB128 varints. Lists of values are a
+//!   length followed by each value zigzag encoded, so that small negative
+//!   numbers stay small.
+//!
+//! A program's contents are a single list. A snapshot's are the ip, relative
+//! base (zigzag encoded), step count, output count, halted flag (0 or 1),
+//! input, output and memory, in that order.
+//!
+//! Version 1 is the same, except that snapshots have no halted flag. Both
+//! versions can be read.
+
+use super::snapshot::Snapshot;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+pub const PROGRAM_MAGIC: &[u8; 4] = b"\x7fICP";
+pub const SNAPSHOT_MAGIC: &[u8; 4] = b"\x7fICS";
+const VERSION: u8 = 2;
+const WORD_SIZE: u8 = 8;
+
+#[derive(Debug)]
+pub enum BinaryError {
+    Io(io::Error),
+    /// The data doesn't start with the expected magic number.
+    BadMagic,
+    UnsupportedVersion(u8),
+    /// The word size is zero, or wider than an `i64`.
+    UnsupportedWordSize(u8),
+    /// A value doesn't fit in the declared word size.
+    ValueTooWide {
+        value: i64,
+        word_size: u8,
+    },
+    /// The data ends partway through.
+    Truncated,
+    /// A varint runs on for longer than a 64 bit value can.
+    BadVarint,
+    /// A flag is something other than 0 or 1.
+    BadFlag(u64),
+    /// An address or count is too big for this platform's `usize`.
+    TooLarge(u64),
+    /// There's more data after the end of the contents.
+    TrailingData,
+}
+
+impl fmt::Display for BinaryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BinaryError::Io(e) => write!(f, "{}", e),
+            BinaryError::BadMagic => write!(f, "not a binary intcode file"),
+            BinaryError::UnsupportedVersion(v) => write!(f, "unsupported format version {}", v),
+            BinaryError::UnsupportedWordSize(w) => write!(f, "unsupported word size {}", w),
+            BinaryError::ValueTooWide { value, word_size } => write!(
+                f,
+                "value {} doesn't fit in the word size of {} bytes",
+                value, word_size
+            ),
+            BinaryError::Truncated => write!(f, "unexpected end of data"),
+            BinaryError::BadVarint => write!(f, "malformed varint"),
+            BinaryError::BadFlag(v) => write!(f, "flag {} isn't 0 or 1", v),
+            BinaryError::TooLarge(v) => write!(f, "{} is too large for this platform", v),
+            BinaryError::TrailingData => write!(f, "unexpected data after the end"),
+        }
+    }
+}
+
+impl std::error::Error for BinaryError {}
+
+impl From<io::Error> for BinaryError {
+    fn from(e: io::Error) -> Self {
+        BinaryError::Io(e)
+    }
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    fn new(magic: &[u8; 4]) -> Self {
+        let mut bytes = magic.to_vec();
+        bytes.extend(&[VERSION, WORD_SIZE]);
+        Encoder { bytes }
+    }
+
+    fn flag(&mut self, value: bool) {
+        self.unsigned(value as u64);
+    }
+
+    fn unsigned(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+
+    fn signed(&mut self, value: i64) {
+        self.unsigned(zigzag(value));
+    }
+
+    fn list(&mut self, values: &[i64]) {
+        self.unsigned(values.len() as u64);
+        for &value in values {
+            self.signed(value);
+        }
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    version: u8,
+    word_size: u8,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(bytes: &'a [u8], magic: &[u8; 4]) -> Result<Self, BinaryError> {
+        match bytes {
+            [m0, m1, m2, m3, version, word_size, rest @ ..] => {
+                if [*m0, *m1, *m2, *m3] != *magic {
+                    Err(BinaryError::BadMagic)
+                } else if *version == 0 || *version > VERSION {
+                    Err(BinaryError::UnsupportedVersion(*version))
+                } else if *word_size == 0 || *word_size > WORD_SIZE {
+                    Err(BinaryError::UnsupportedWordSize(*word_size))
+                } else {
+                    Ok(Decoder {
+                        bytes: rest,
+                        version: *version,
+                        word_size: *word_size,
+                    })
+                }
+            }
+            _ if !bytes.starts_with(&magic[..bytes.len().min(4)]) => Err(BinaryError::BadMagic),
+            _ => Err(BinaryError::Truncated),
+        }
+    }
+
+    fn unsigned(&mut self) -> Result<u64, BinaryError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = self.bytes.split_first().ok_or(BinaryError::Truncated)?;
+            self.bytes = rest;
+            // The tenth byte only has room for the top bit.
+            if shift == 63 && byte > 1 {
+                return Err(BinaryError::BadVarint);
+            }
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(BinaryError::BadVarint)
+    }
+
+    fn flag(&mut self) -> Result<bool, BinaryError> {
+        match self.unsigned()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            value => Err(BinaryError::BadFlag(value)),
+        }
+    }
+
+    fn size(&mut self) -> Result<usize, BinaryError> {
+        let value = self.unsigned()?;
+        usize::try_from(value).map_err(|_| BinaryError::TooLarge(value))
+    }
+
+    fn signed(&mut self) -> Result<i64, BinaryError> {
+        let value = unzigzag(self.unsigned()?);
+        let bits = u32::from(self.word_size) * 8;
+        // shifting out everything but the word's bits and back again only
+        // leaves the value as it was if it fits in them
+        if (value << (64 - bits)) >> (64 - bits) != value {
+            return Err(BinaryError::ValueTooWide {
+                value,
+                word_size: self.word_size,
+            });
+        }
+        Ok(value)
+    }
+
+    fn list(&mut self) -> Result<Vec<i64>, BinaryError> {
+        let len = self.unsigned()?;
+        // Every value takes at least a byte, which stops a corrupt length
+        // from reserving more memory than the data could possibly fill.
+        let mut values = Vec::with_capacity((len as usize).min(self.bytes.len()));
+        for _ in 0..len {
+            values.push(self.signed()?);
+        }
+        Ok(values)
+    }
+
+    fn finish(self) -> Result<(), BinaryError> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(BinaryError::TrailingData)
+        }
+    }
+}
+
+/// Whether `bytes` look like a binary program, as opposed to text.
+pub fn is_binary_program(bytes: &[u8]) -> bool {
+    bytes.starts_with(PROGRAM_MAGIC)
+}
+
+/// Writes `program` in the binary format.
+pub fn save(program: &[i64], mut out: impl Write) -> io::Result<()> {
+    let mut encoder = Encoder::new(PROGRAM_MAGIC);
+    encoder.list(program);
+    out.write_all(&encoder.bytes)?;
+    out.flush()
+}
+
+/// Reads a program written by `save`.
+pub fn load(mut input: impl Read) -> Result<Vec<i64>, BinaryError> {
+    let mut bytes = vec![];
+    input.read_to_end(&mut bytes)?;
+    from_bytes(&bytes)
+}
+
+/// Decodes a program from the bytes of a binary file.
+pub fn from_bytes(bytes: &[u8]) -> Result<Vec<i64>, BinaryError> {
+    let mut decoder = Decoder::new(bytes, PROGRAM_MAGIC)?;
+    let program = decoder.list()?;
+    decoder.finish()?;
+    Ok(program)
+}
+
+impl Snapshot {
+    /// Writes the snapshot in the binary format.
+    pub fn save_binary(&self, mut out: impl Write) -> io::Result<()> {
+        let mut encoder = Encoder::new(SNAPSHOT_MAGIC);
+        encoder.unsigned(self.ip as u64);
+        encoder.signed(self.relative_base);
+        encoder.unsigned(self.steps);
+        encoder.unsigned(self.outputs as u64);
+        encoder.flag(self.halted);
+        encoder.list(&self.input);
+        encoder.list(&self.output);
+        encoder.list(&self.memory);
+        out.write_all(&encoder.bytes)?;
+        out.flush()
+    }
+
+    /// Reads a snapshot written by `save_binary`.
+    pub fn load_binary(mut input: impl Read) -> Result<Self, BinaryError> {
+        let mut bytes = vec![];
+        input.read_to_end(&mut bytes)?;
+        let mut decoder = Decoder::new(&bytes, SNAPSHOT_MAGIC)?;
+        let snapshot = Snapshot {
+            ip: decoder.size()?,
+            relative_base: decoder.signed()?,
+            steps: decoder.unsigned()?,
+            outputs: decoder.size()?,
+            halted: decoder.version >= 2 && decoder.flag()?,
+            input: decoder.list()?,
+            output: decoder.list()?,
+            memory: decoder.list()?,
+        };
+        decoder.finish()?;
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod binary_tests {
+    use super::{from_bytes, load, save, BinaryError};
+    use crate::intcode::snapshot::Snapshot;
+    use crate::intcode::Machine;
+
+    fn encode(program: &[i64]) -> Vec<u8> {
+        let mut bytes = vec![];
+        save(program, &mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_encoding() {
+        assert_eq!(
+            encode(&[1, -1, 64, 0]),
+            b"\x7fICP\x02\x08\x04\x02\x01\x80\x01\x00".to_vec()
+        );
+        // version 1 programs are laid out the same
+        assert_eq!(
+            from_bytes(b"\x7fICP\x01\x08\x04\x02\x01\x80\x01\x00").unwrap(),
+            vec![1, -1, 64, 0]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let program = vec![
+            109,
+            1,
+            204,
+            -1,
+            1001,
+            100,
+            1,
+            100,
+            1008,
+            100,
+            16,
+            101,
+            1006,
+            101,
+            0,
+            99,
+            i64::MAX,
+            i64::MIN,
+            1125899906842624,
+        ];
+        assert_eq!(load(&encode(&program)[..]).unwrap(), program);
+        assert_eq!(load(&encode(&[])[..]).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let bytes = encode(&[1, 2, 300]);
+        assert!(matches!(from_bytes(b"1,2,3"), Err(BinaryError::BadMagic)));
+        assert!(matches!(from_bytes(b"\x7fIC"), Err(BinaryError::Truncated)));
+        assert!(matches!(
+            from_bytes(b"\x7fICP\x03\x08\x00"),
+            Err(BinaryError::UnsupportedVersion(3))
+        ));
+        assert!(matches!(
+            from_bytes(b"\x7fICP\x00\x08\x00"),
+            Err(BinaryError::UnsupportedVersion(0))
+        ));
+        assert!(matches!(
+            from_bytes(b"\x7fICP\x01\x10\x00"),
+            Err(BinaryError::UnsupportedWordSize(16))
+        ));
+        assert!(matches!(
+            from_bytes(&bytes[..bytes.len() - 1]),
+            Err(BinaryError::Truncated)
+        ));
+        assert!(matches!(
+            from_bytes(&[&bytes[..], &[0]].concat()),
+            Err(BinaryError::TrailingData)
+        ));
+        assert!(matches!(
+            from_bytes(b"\x7fICP\x01\x00\x00"),
+            Err(BinaryError::UnsupportedWordSize(0))
+        ));
+        // 127 and -128 fit in a byte, but 128 doesn't.
+        let narrow = [&b"\x7fICP\x02\x01"[..], &encode(&[127, -128])[6..]].concat();
+        assert_eq!(from_bytes(&narrow).unwrap(), vec![127, -128]);
+        let narrow = [&b"\x7fICP\x02\x01"[..], &encode(&[127, 128])[6..]].concat();
+        assert!(matches!(
+            from_bytes(&narrow),
+            Err(BinaryError::ValueTooWide {
+                value: 128,
+                word_size: 1
+            })
+        ));
+        let overlong = b"\x7fICP\x02\x08\x01\xff\xff\xff\xff\xff\xff\xff\xff\xff\x7f";
+        assert!(matches!(from_bytes(overlong), Err(BinaryError::BadVarint)));
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut machine = Machine::new(vec![109, -3, 3, 0, 4, 0, 99]);
+        machine.push_input(5);
+        machine.push_input(6);
+        machine.run().unwrap();
+        let snapshot = machine.snapshot();
+
+        let mut bytes = vec![];
+        snapshot.save_binary(&mut bytes).unwrap();
+        assert_eq!(Snapshot::load_binary(&bytes[..]).unwrap(), snapshot);
+        assert!(matches!(
+            Snapshot::load_binary(&encode(&[1])[..]),
+            Err(BinaryError::BadMagic)
+        ));
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "32")]
+    fn test_snapshot_ip_too_large() {
+        // an ip of 2^32, then the rest of an empty snapshot
+        let bytes = b"\x7fICS\x02\x08\x80\x80\x80\x80\x10\x00\x00\x00\x00\x00\x00\x00";
+        assert!(matches!(
+            Snapshot::load_binary(&bytes[..]),
+            Err(BinaryError::TooLarge(4294967296))
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_halted() {
+        let mut machine = Machine::new(vec![104, 7, 99]);
+        machine.run_with_io(&mut vec![], &mut vec![]).unwrap();
+        let snapshot = machine.snapshot();
+        let mut bytes = vec![];
+        snapshot.save_binary(&mut bytes).unwrap();
+        assert_eq!(Snapshot::load_binary(&bytes[..]).unwrap(), snapshot);
+
+        // ip 2, relative base 0, 2 steps, 1 output, halted, then three lists
+        assert_eq!(&bytes[6..11], b"\x02\x00\x02\x01\x01");
+        let mut bad = bytes.clone();
+        bad[10] = 2;
+        assert!(matches!(
+            Snapshot::load_binary(&bad[..]),
+            Err(BinaryError::BadFlag(2))
+        ));
+
+        // a version 1 snapshot is the same without the flag
+        let v1 = [&b"\x7fICS\x01\x08"[..], &bytes[6..10], &bytes[11..]].concat();
+        let loaded = Snapshot::load_binary(&v1[..]).unwrap();
+        assert!(!loaded.halted);
+        assert_eq!(loaded.memory, snapshot.memory);
+    }
+}
@@ -0,0 +1,99 @@
+//! A cache of decoded instructions, so loops don't decode the same headers and
+//! params over and over.
+//!
+//! Entries are keyed by address. Since intcode programs are free to rewrite
+//! their own code, every write to memory drops any entry whose instruction
+//! could cover the written address.
+
+use super::{read_instruction, IntcodeError, Op};
+
+/// The widest instruction, in values. A write to `address` can affect any
+/// instruction starting up to this many values before it.
+const MAX_WIDTH: usize = 4;
+
+#[derive(Debug, Clone)]
+pub(super) struct DecodeCache {
+    ops: Vec<Option<Op>>,
+    enabled: bool,
+}
+
+impl Default for DecodeCache {
+    fn default() -> Self {
+        DecodeCache {
+            ops: vec![],
+            enabled: true,
+        }
+    }
+}
+
+impl DecodeCache {
+    /// A cache which never holds anything, so every decode reads memory.
+    pub(super) fn disabled() -> Self {
+        DecodeCache {
+            ops: vec![],
+            enabled: false,
+        }
+    }
+
+    /// Decodes the instruction at `address`, reusing an earlier decode if
+    /// nothing has been written over it since.
+    pub(super) fn decode(&mut self, address: usize, memory: &[i64]) -> Result<Op, IntcodeError> {
+        if let Some(Some(op)) = self.ops.get(address) {
+            return Ok(*op);
+        }
+        let op = read_instruction(address, memory)?;
+        if self.enabled {
+            if address >= self.ops.len() {
+                self.ops.resize(address + 1, None);
+            }
+            self.ops[address] = Some(op);
+        }
+        Ok(op)
+    }
+
+    /// Forgets every instruction which might include `address`.
+    pub(super) fn invalidate(&mut self, address: usize) {
+        let start = address.saturating_sub(MAX_WIDTH - 1);
+        for op in self.ops.iter_mut().take(address + 1).skip(start) {
+            *op = None;
+        }
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.ops.clear();
+    }
+}
+
+#[cfg(test)]
+mod cache_tests {
+    use crate::intcode::{Machine, Status};
+
+    #[test]
+    fn test_self_modifying_loop() {
+        // Outputs 5, then rewrites its own `out` into an immediate-mode one
+        // (104) and jumps back, so the second pass must see the new header.
+        let program = vec![4, 9, 1101, 100, 4, 0, 1105, 1, 0, 5];
+        let mut machine = Machine::new(program);
+        assert_eq!(machine.run(), Ok(Status::Output(5)));
+        assert_eq!(machine.run(), Ok(Status::Output(9)));
+    }
+
+    #[test]
+    fn test_poke_invalidates() {
+        // Outputs 1 forever, until poked.
+        let mut machine = Machine::new(vec![104, 1, 1105, 1, 0]);
+        assert_eq!(machine.run(), Ok(Status::Output(1)));
+        machine.poke(1, 2);
+        assert_eq!(machine.run(), Ok(Status::Output(2)));
+    }
+
+    #[test]
+    fn test_step_back_invalidates() {
+        let mut machine = Machine::new(vec![4, 9, 1101, 100, 4, 0, 1105, 1, 0, 5]);
+        machine.enable_history(None);
+        assert_eq!(machine.run(), Ok(Status::Output(5)));
+        assert_eq!(machine.run(), Ok(Status::Output(9)));
+        machine.rewind(4);
+        assert_eq!(machine.run(), Ok(Status::Output(5)));
+    }
+}
@@ -0,0 +1,513 @@
+//! Static control-flow analysis of intcode programs.
+//!
+//! Starting from address 0, every instruction the program could reach is
+//! decoded, following each jump whose target is an immediate value. The
+//! instructions are then grouped into basic blocks: straight-line runs which
+//! are only ever entered at the top and only branch at the bottom.
+//!
+//! Intcode makes this harder than usual in a few ways, each of which is
+//! reported as an `Issue` rather than treated as an error:
+//!
+//! - jumps through memory or the relative base can go anywhere, so the
+//!   analysis can't follow them, and an immediate jump can name a negative
+//!   address, which fails when taken;
+//! - nothing stops two reachable instructions from sharing memory, or a
+//!   program from reading or writing its own code as data;
+//! - a reachable address might not hold a valid instruction at all.
+//!
+//! Relative-mode params are resolved where the relative base is the same on
+//! every path to an instruction, which covers programs that only ever adjust
+//! it by immediate values outside of loops.
+//!
+//! `Cfg::to_dot` renders the graph for Graphviz.
+
+use super::{read_instruction, IntcodeError, Op, Param};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Write};
+
+/// A way control can leave the end of a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    /// On to the next instruction in memory.
+    Fallthrough(usize),
+    /// A jump taken to an immediate target.
+    Jump(usize),
+}
+
+impl Edge {
+    pub fn target(&self) -> usize {
+        match self {
+            Edge::Fallthrough(target) | Edge::Jump(target) => *target,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub start: usize,
+    pub instructions: Vec<(usize, Op)>,
+    pub successors: Vec<Edge>,
+    /// Whether the block ends in a jump whose target isn't known statically.
+    pub indirect: bool,
+}
+
+/// Something about the program the analysis couldn't make sense of, or which
+/// is worth a closer look.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    /// The jump at `address` goes through memory, so its target is unknown.
+    IndirectJump { address: usize },
+    /// The jump at `address` has an immediate target below zero.
+    NegativeJump { address: usize, target: i64 },
+    /// Control can reach `address`, but it doesn't hold a valid instruction.
+    Undecodable { address: usize, error: IntcodeError },
+    /// The instructions at `address` and `other` share memory.
+    Overlap { address: usize, other: usize },
+    /// The instruction at `address` writes into the instruction at `target`.
+    WriteToCode { address: usize, target: usize },
+    /// The instruction at `address` reads part of the instruction at `target`
+    /// as data.
+    ReadsCode { address: usize, target: usize },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Issue::IndirectJump { address } => write!(f, "indirect jump at {}", address),
+            Issue::NegativeJump { address, target } => {
+                write!(f, "jump at {} to negative address {}", address, target)
+            }
+            Issue::Undecodable { address, error } => {
+                write!(f, "reachable address {} doesn't decode: {}", address, error)
+            }
+            Issue::Overlap { address, other } => write!(
+                f,
+                "instructions at {} and {} overlap",
+                other.min(address),
+                other.max(address)
+            ),
+            Issue::WriteToCode { address, target } => {
+                write!(f, "instruction at {} writes to code at {}", address, target)
+            }
+            Issue::ReadsCode { address, target } => {
+                write!(f, "instruction at {} reads code at {}", address, target)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cfg {
+    /// Every basic block, keyed by starting address.
+    pub blocks: BTreeMap<usize, Block>,
+    pub issues: Vec<Issue>,
+}
+
+/// The direct successors of a single instruction, and the issue with its
+/// jump if the target can't be followed.
+fn successors(address: usize, op: &Op) -> (Vec<Edge>, Option<Issue>) {
+    let next = Edge::Fallthrough(address + op.width());
+    let (cond, target, jump_if) = match *op {
+        Op::Halt => return (vec![], None),
+        Op::JumpIfTrue { cond, target } => (cond, target, true),
+        Op::JumpIfFalse { cond, target } => (cond, target, false),
+        _ => return (vec![next], None),
+    };
+    let (taken, falls) = match cond {
+        Param::Immediate(value) => ((value != 0) == jump_if, (value != 0) != jump_if),
+        _ => (true, true),
+    };
+    let mut edges = vec![];
+    let mut issue = None;
+    if taken {
+        match target {
+            Param::Immediate(target) if target >= 0 => edges.push(Edge::Jump(target as usize)),
+            Param::Immediate(target) => issue = Some(Issue::NegativeJump { address, target }),
+            _ => issue = Some(Issue::IndirectJump { address }),
+        }
+    }
+    if falls {
+        edges.push(next);
+    }
+    (edges, issue)
+}
+
+/// The params an instruction reads as values, and the one it writes to.
+fn operands(op: &Op) -> (Vec<Param>, Option<Param>) {
+    match *op {
+        Op::Add { a, b, out }
+        | Op::Multiply { a, b, out }
+        | Op::LessThan { a, b, out }
+        | Op::Equals { a, b, out } => (vec![a, b], Some(out)),
+        Op::Input { out } => (vec![], Some(out)),
+        _ => (op.params(), None),
+    }
+}
+
+/// The memory address `param` refers to, given the relative base if known.
+fn address_of(param: Param, base: Option<i64>) -> Option<usize> {
+    match param {
+        Param::Position(address) => Some(address),
+        Param::Relative(offset) => base
+            .and_then(|base| base.checked_add(offset))
+            .filter(|&address| address >= 0)
+            .map(|address| address as usize),
+        Param::Immediate(_) => None,
+    }
+}
+
+/// The relative base at each reachable instruction, or `None` where it isn't
+/// the same on every path from address 0.
+fn relative_bases(
+    ops: &BTreeMap<usize, Op>,
+    edges: &BTreeMap<usize, Vec<Edge>>,
+) -> BTreeMap<usize, Option<i64>> {
+    let mut bases: BTreeMap<usize, Option<i64>> = BTreeMap::new();
+    let mut work = vec![(0, Some(0))];
+    while let Some((address, base)) = work.pop() {
+        let op = match ops.get(&address) {
+            Some(op) => op,
+            None => continue,
+        };
+        let base = match bases.get(&address) {
+            Some(&known) if known == base || known.is_none() => continue,
+            Some(_) => None,
+            None => base,
+        };
+        bases.insert(address, base);
+        let after = match *op {
+            Op::AdjustRelativeBase {
+                value: Param::Immediate(value),
+            } => base.and_then(|base| base.checked_add(value)),
+            Op::AdjustRelativeBase { .. } => None,
+            _ => base,
+        };
+        work.extend(edges[&address].iter().map(|edge| (edge.target(), after)));
+    }
+    bases
+}
+
+impl Cfg {
+    /// Analyzes `program` from address 0.
+    pub fn build(program: &[i64]) -> Self {
+        let mut ops: BTreeMap<usize, Op> = BTreeMap::new();
+        let mut edges: BTreeMap<usize, Vec<Edge>> = BTreeMap::new();
+        // Which instruction each memory cell belongs to.
+        let mut owners: BTreeMap<usize, usize> = BTreeMap::new();
+        let mut undecodable = BTreeSet::new();
+        let mut issues = vec![];
+        let mut indirect = BTreeSet::new();
+        // Jumps the analysis couldn't follow, which always end a block.
+        let mut unfollowed = BTreeSet::new();
+
+        let mut work = vec![0];
+        while let Some(address) = work.pop() {
+            if ops.contains_key(&address) || undecodable.contains(&address) {
+                continue;
+            }
+            let op = match read_instruction(address, program) {
+                Ok(op) => op,
+                Err(error) => {
+                    undecodable.insert(address);
+                    issues.push(Issue::Undecodable { address, error });
+                    continue;
+                }
+            };
+            let mut overlapping = BTreeSet::new();
+            for cell in address..address + op.width() {
+                match owners.get(&cell) {
+                    Some(&other) => {
+                        overlapping.insert(other);
+                    }
+                    None => {
+                        owners.insert(cell, address);
+                    }
+                }
+            }
+            issues.extend(
+                overlapping
+                    .into_iter()
+                    .map(|other| Issue::Overlap { address, other }),
+            );
+
+            let (next, issue) = successors(address, &op);
+            if let Some(issue) = issue {
+                if let Issue::IndirectJump { .. } = issue {
+                    indirect.insert(address);
+                }
+                unfollowed.insert(address);
+                issues.push(issue);
+            }
+            work.extend(next.iter().rev().map(Edge::target));
+            ops.insert(address, op);
+            edges.insert(address, next);
+        }
+
+        let bases = relative_bases(&ops, &edges);
+        for (&address, op) in &ops {
+            let base = bases.get(&address).copied().flatten();
+            let owner = |param| address_of(param, base).and_then(|cell| owners.get(&cell));
+            let (reads, write) = operands(op);
+            let mut read_from = BTreeSet::new();
+            read_from.extend(reads.into_iter().filter_map(owner));
+            issues.extend(
+                read_from
+                    .into_iter()
+                    .map(|&target| Issue::ReadsCode { address, target }),
+            );
+            if let Some(&target) = write.and_then(owner) {
+                issues.push(Issue::WriteToCode { address, target });
+            }
+        }
+
+        // An instruction starts a block unless it's only reachable by
+        // unconditionally falling through from the one before it.
+        let mut predecessors: BTreeMap<usize, Vec<(usize, Edge)>> = BTreeMap::new();
+        for (&from, out) in &edges {
+            for &edge in out {
+                predecessors
+                    .entry(edge.target())
+                    .or_default()
+                    .push((from, edge));
+            }
+        }
+        let is_leader = |address: usize| match predecessors.get(&address).map(Vec::as_slice) {
+            Some([(from, Edge::Fallthrough(_))]) => {
+                edges[from].len() != 1 || unfollowed.contains(from)
+            }
+            _ => true,
+        };
+
+        let mut blocks = BTreeMap::new();
+        for &start in ops.keys().filter(|&&address| is_leader(address)) {
+            let mut instructions = vec![];
+            let mut address = start;
+            loop {
+                instructions.push((address, ops[&address]));
+                match edges[&address].as_slice() {
+                    [Edge::Fallthrough(next)]
+                        if ops.contains_key(next)
+                            && !is_leader(*next)
+                            && !unfollowed.contains(&address) =>
+                    {
+                        address = *next
+                    }
+                    _ => break,
+                }
+            }
+            blocks.insert(
+                start,
+                Block {
+                    start,
+                    instructions,
+                    successors: edges[&address].clone(),
+                    indirect: indirect.contains(&address),
+                },
+            );
+        }
+
+        Cfg { blocks, issues }
+    }
+
+    /// Renders the graph in Graphviz's DOT language. Jumps are labelled,
+    /// unknown jump targets are drawn as `?`, and addresses which don't decode
+    /// are drawn in red.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        writeln!(dot, "digraph cfg {{").unwrap();
+        writeln!(dot, "    node [shape=box, fontname=monospace];").unwrap();
+        for block in self.blocks.values() {
+            let label: String = block
+                .instructions
+                .iter()
+                .map(|(address, op)| format!("{:>5}: {}\\l", address, op))
+                .collect();
+            writeln!(dot, "    b{} [label=\"{}\"];", block.start, label).unwrap();
+            for edge in &block.successors {
+                let target = edge.target();
+                let node = if self.blocks.contains_key(&target) {
+                    format!("b{}", target)
+                } else {
+                    format!("bad{}", target)
+                };
+                match edge {
+                    Edge::Fallthrough(_) => writeln!(dot, "    b{} -> {};", block.start, node),
+                    Edge::Jump(_) => {
+                        writeln!(dot, "    b{} -> {} [label=jump];", block.start, node)
+                    }
+                }
+                .unwrap();
+            }
+            if block.indirect {
+                writeln!(
+                    dot,
+                    "    unknown{} [label=\"?\", shape=circle];",
+                    block.start
+                )
+                .unwrap();
+                writeln!(
+                    dot,
+                    "    b{} -> unknown{} [style=dashed];",
+                    block.start, block.start
+                )
+                .unwrap();
+            }
+        }
+        for issue in &self.issues {
+            if let Issue::Undecodable { address, .. } = issue {
+                writeln!(
+                    dot,
+                    "    bad{} [label=\"{}: ???\", color=red];",
+                    address, address
+                )
+                .unwrap();
+            }
+        }
+        dot.push('}');
+        dot.push('\n');
+        dot
+    }
+}
+
+#[cfg(test)]
+mod cfg_tests {
+    use super::{Cfg, Edge, Issue};
+
+    #[test]
+    fn test_straight_line() {
+        let cfg = Cfg::build(&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+        assert_eq!(cfg.blocks.len(), 1);
+        let block = &cfg.blocks[&0];
+        assert_eq!(block.instructions.len(), 3);
+        assert!(block.successors.is_empty());
+        assert_eq!(
+            cfg.issues,
+            vec![
+                Issue::WriteToCode {
+                    address: 0,
+                    target: 0
+                },
+                // `mul [3], [11], [0]` reads back the result written into the add
+                Issue::ReadsCode {
+                    address: 4,
+                    target: 0
+                },
+                Issue::WriteToCode {
+                    address: 4,
+                    target: 0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_branches() {
+        // Day 5's "is the input 8?" program.
+        let cfg = Cfg::build(&[
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99,
+        ]);
+        assert!(cfg.issues.is_empty());
+        assert_eq!(
+            cfg.blocks.keys().copied().collect::<Vec<_>>(),
+            vec![0, 9, 16, 22, 31, 36, 46]
+        );
+        assert_eq!(
+            cfg.blocks[&0].successors,
+            vec![Edge::Jump(22), Edge::Fallthrough(9)]
+        );
+        // `1106, 0, 36` always jumps, so it has no fallthrough.
+        assert_eq!(cfg.blocks[&16].successors, vec![Edge::Jump(36)]);
+        assert_eq!(cfg.blocks[&46].instructions.len(), 1);
+    }
+
+    #[test]
+    fn test_issues() {
+        // Jumps through [7], then into the middle of its own jump.
+        let cfg = Cfg::build(&[5, 7, 7, 1105, 1, 4, 99, 0]);
+        assert!(cfg.blocks[&0].indirect);
+        assert!(cfg.issues.contains(&Issue::IndirectJump { address: 0 }));
+        assert!(cfg.issues.contains(&Issue::Overlap {
+            address: 4,
+            other: 3
+        }));
+        // 4 is `1, 4, 99, 0`: add [4], [99], [0], then 8 doesn't decode.
+        assert!(cfg.issues.contains(&Issue::WriteToCode {
+            address: 4,
+            target: 0
+        }));
+        assert!(cfg
+            .issues
+            .iter()
+            .any(|issue| matches!(issue, Issue::Undecodable { address: 8, .. })));
+
+        let cfg = Cfg::build(&[1105, 1, -3]);
+        assert_eq!(
+            cfg.issues,
+            &[Issue::NegativeJump {
+                address: 0,
+                target: -3
+            }]
+        );
+        assert!(cfg.blocks[&0].successors.is_empty());
+        assert_eq!(
+            cfg.issues[0].to_string(),
+            "jump at 0 to negative address -3"
+        );
+    }
+
+    #[test]
+    fn test_negative_conditional_jump_ends_block() {
+        // `jt [7], #-1` can only fall through, but still ends its block.
+        let cfg = Cfg::build(&[1005, 7, -1, 104, 1, 99, 0, 0]);
+        assert_eq!(cfg.blocks.keys().copied().collect::<Vec<_>>(), vec![0, 3]);
+        assert_eq!(cfg.blocks[&0].instructions.len(), 1);
+        assert_eq!(cfg.blocks[&0].successors, vec![Edge::Fallthrough(3)]);
+        assert!(!cfg.blocks[&0].indirect);
+        assert_eq!(cfg.blocks[&3].instructions.len(), 2);
+    }
+
+    #[test]
+    fn test_relative_access_to_code() {
+        // arb #4; in rb+1 (address 5, inside the `out`); out rb-4 (address 0);
+        // hlt
+        let cfg = Cfg::build(&[109, 4, 203, 1, 204, -4, 99]);
+        assert_eq!(
+            cfg.issues,
+            vec![
+                Issue::WriteToCode {
+                    address: 2,
+                    target: 4
+                },
+                Issue::ReadsCode {
+                    address: 4,
+                    target: 0
+                },
+            ]
+        );
+        assert_eq!(
+            cfg.issues[1].to_string(),
+            "instruction at 4 reads code at 0"
+        );
+
+        // Once the base depends on the path taken, relative params are left
+        // alone.
+        let cfg = Cfg::build(&[1005, 11, 5, 109, 4, 204, -4, 99, 0, 0, 0, 1]);
+        assert!(cfg.issues.is_empty());
+    }
+
+    #[test]
+    fn test_dot() {
+        let dot = Cfg::build(&[1105, 1, 4, 0, 99]).to_dot();
+        assert_eq!(
+            dot,
+            "digraph cfg {\n    \
+             node [shape=box, fontname=monospace];\n    \
+             b0 [label=\"    0: jt #1, #4\\l\"];\n    \
+             b0 -> b4 [label=jump];\n    \
+             b4 [label=\"    4: hlt\\l\"];\n\
+             }\n"
+        );
+    }
+}
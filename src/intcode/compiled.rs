@@ -0,0 +1,292 @@
+//! An alternate execution engine which compiles instructions into closures.
+//!
+//! The first time an address is executed, its instruction is decoded once and
+//! turned into a closure with its params already bound, so running it again
+//! costs a single indirect call rather than a decode and a dispatch. Compiled
+//! closures are kept in a table indexed by address.
+//!
+//! Intcode programs can rewrite their own code, which would leave stale
+//! closures behind. Every address a compiled instruction occupies is marked,
+//! and as soon as a write lands on one the engine hands its state over to a
+//! regular `Machine` and interprets the rest of the program. Writes to memory
+//! which hasn't been compiled yet are harmless, since compilation reads
+//! whatever is there when the address is first reached.
+//!
+//! `compute` and `compute_with_io` here behave exactly like their counterparts
+//! in `intcode`, down to which error is reported for a broken program.
+
+use super::{
+    jump_target, load, param_address, read_instruction, store, InputSource, IntcodeError, Machine,
+    Op, OutputSink, Param, StdinSource, StdoutSink,
+};
+use std::rc::Rc;
+
+/// Where to go after executing an instruction.
+enum Flow {
+    Next(usize),
+    Halt,
+}
+
+type Compiled = Rc<
+    dyn Fn(&mut Engine, &mut dyn InputSource, &mut dyn OutputSink) -> Result<Flow, IntcodeError>,
+>;
+
+struct Engine {
+    memory: Vec<i64>,
+    relative_base: i64,
+    code: Vec<Option<Compiled>>,
+    /// Addresses occupied by a compiled instruction.
+    compiled: Vec<bool>,
+    /// Set when a write lands on compiled code.
+    modified: bool,
+}
+
+impl Engine {
+    fn new(memory: Vec<i64>) -> Self {
+        Engine {
+            memory,
+            relative_base: 0,
+            code: vec![],
+            compiled: vec![],
+            modified: false,
+        }
+    }
+
+    fn read(&self, ip: usize, param: Param) -> Result<i64, IntcodeError> {
+        match param {
+            Param::Immediate(value) => Ok(value),
+            _ => Ok(load(
+                &self.memory,
+                param_address(ip, param, self.relative_base)?,
+            )),
+        }
+    }
+
+    fn write(&mut self, ip: usize, param: Param, value: i64) -> Result<(), IntcodeError> {
+        let address = param_address(ip, param, self.relative_base)?;
+        store(&mut self.memory, address, value);
+        if self.compiled.get(address) == Some(&true) {
+            self.modified = true;
+        }
+        Ok(())
+    }
+
+    /// The closure for the instruction at `ip`, compiling it if need be.
+    fn fetch(&mut self, ip: usize) -> Result<Compiled, IntcodeError> {
+        if let Some(Some(compiled)) = self.code.get(ip) {
+            return Ok(Rc::clone(compiled));
+        }
+        let op = read_instruction(ip, &self.memory)?;
+        let end = ip + op.width();
+        if end > self.compiled.len() {
+            self.compiled.resize(end, false);
+        }
+        for flag in &mut self.compiled[ip..end] {
+            *flag = true;
+        }
+        if ip >= self.code.len() {
+            self.code.resize(ip + 1, None);
+        }
+        let compiled = compile(ip, op);
+        self.code[ip] = Some(Rc::clone(&compiled));
+        Ok(compiled)
+    }
+}
+
+/// Binds the instruction at `ip` into a closure which executes it.
+fn compile(ip: usize, op: Op) -> Compiled {
+    let next = ip + op.width();
+    match op {
+        Op::Add { a, b, out } => Rc::new(move |e, _, _| {
+            let value = e.read(ip, a)?.checked_add(e.read(ip, b)?);
+            let value = value.ok_or(IntcodeError::Overflow { address: ip })?;
+            e.write(ip, out, value)?;
+            Ok(Flow::Next(next))
+        }),
+        Op::Multiply { a, b, out } => Rc::new(move |e, _, _| {
+            let value = e.read(ip, a)?.checked_mul(e.read(ip, b)?);
+            let value = value.ok_or(IntcodeError::Overflow { address: ip })?;
+            e.write(ip, out, value)?;
+            Ok(Flow::Next(next))
+        }),
+        Op::Input { out } => Rc::new(move |e, input, _| {
+            let value = input
+                .next_input()
+                .ok_or(IntcodeError::InputExhausted { address: ip })?;
+            e.write(ip, out, value)?;
+            Ok(Flow::Next(next))
+        }),
+        Op::Output { value } => Rc::new(move |e, _, output| {
+            output.send_output(e.read(ip, value)?);
+            Ok(Flow::Next(next))
+        }),
+        Op::JumpIfTrue { cond, target } => Rc::new(move |e, _, _| {
+            if e.read(ip, cond)? != 0 {
+                return Ok(Flow::Next(jump_target(ip, e.read(ip, target)?)?));
+            }
+            Ok(Flow::Next(next))
+        }),
+        Op::JumpIfFalse { cond, target } => Rc::new(move |e, _, _| {
+            if e.read(ip, cond)? == 0 {
+                return Ok(Flow::Next(jump_target(ip, e.read(ip, target)?)?));
+            }
+            Ok(Flow::Next(next))
+        }),
+        Op::LessThan { a, b, out } => Rc::new(move |e, _, _| {
+            let value = (e.read(ip, a)? < e.read(ip, b)?) as i64;
+            e.write(ip, out, value)?;
+            Ok(Flow::Next(next))
+        }),
+        Op::Equals { a, b, out } => Rc::new(move |e, _, _| {
+            let value = (e.read(ip, a)? == e.read(ip, b)?) as i64;
+            e.write(ip, out, value)?;
+            Ok(Flow::Next(next))
+        }),
+        Op::AdjustRelativeBase { value } => Rc::new(move |e, _, _| {
+            let base = e.relative_base.checked_add(e.read(ip, value)?);
+            e.relative_base = base.ok_or(IntcodeError::Overflow { address: ip })?;
+            Ok(Flow::Next(next))
+        }),
+        Op::Halt => Rc::new(|_, _, _| Ok(Flow::Halt)),
+    }
+}
+
+/// Run an intcode program with the compiling engine, prompting on stdin for
+/// input and printing output.
+pub fn compute(data: &mut Vec<i64>) -> Result<(), IntcodeError> {
+    compute_with_io(data, &mut StdinSource, &mut StdoutSink)
+}
+
+/// Run an intcode program with the compiling engine, reading input from
+/// `input` and sending output to `output`.
+pub fn compute_with_io(
+    data: &mut Vec<i64>,
+    input: &mut impl InputSource,
+    output: &mut impl OutputSink,
+) -> Result<(), IntcodeError> {
+    let mut engine = Engine::new(std::mem::take(data));
+    let mut ip = 0;
+    let result = loop {
+        let flow = engine
+            .fetch(ip)
+            .and_then(|compiled| compiled(&mut engine, input, output));
+        match flow {
+            Ok(Flow::Next(next)) => ip = next,
+            Ok(Flow::Halt) => break Ok(()),
+            Err(e) => break Err(e),
+        }
+        if engine.modified {
+            let mut machine = Machine::new(std::mem::take(&mut engine.memory));
+            machine.ip = ip;
+            machine.relative_base = engine.relative_base;
+            let result = machine.run_with_io(input, output);
+            engine.memory = machine.into_memory();
+            break result;
+        }
+    };
+    *data = engine.memory;
+    result
+}
+
+#[cfg(test)]
+mod compiled_tests {
+    use super::compute_with_io;
+    use crate::intcode;
+
+    /// Runs `program` through both engines and checks that the results, the
+    /// final memory and the output all match.
+    fn check(program: &[i64], input: &[i64]) {
+        let mut interpreted = program.to_vec();
+        let mut interpreted_output = vec![];
+        let interpreted_result = intcode::compute_with_io(
+            &mut interpreted,
+            &mut input.to_vec(),
+            &mut interpreted_output,
+        );
+
+        let mut compiled = program.to_vec();
+        let mut compiled_output = vec![];
+        let compiled_result =
+            compute_with_io(&mut compiled, &mut input.to_vec(), &mut compiled_output);
+
+        assert_eq!(compiled_result, interpreted_result, "{:?}", program);
+        assert_eq!(compiled, interpreted, "{:?}", program);
+        assert_eq!(compiled_output, interpreted_output, "{:?}", program);
+    }
+
+    #[test]
+    fn test_day02_examples() {
+        check(&[1, 0, 0, 0, 99], &[]);
+        check(&[2, 3, 0, 3, 99], &[]);
+        check(&[2, 4, 4, 5, 99, 0], &[]);
+        check(&[1, 1, 1, 4, 99, 5, 6, 0, 99], &[]);
+        check(&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50], &[]);
+    }
+
+    #[test]
+    fn test_day05_examples() {
+        check(&[1002, 4, 3, 4, 33], &[]);
+        check(&[3, 0, 4, 0, 99], &[42]);
+        check(&[1107, 1, 2, 5, 99, -1], &[]);
+        check(&[8, 5, 6, 7, 99, 8, 9, -1], &[]);
+        check(&[1105, 1, 4, 99, 1101, 2, 3, 0, 99], &[]);
+        check(&[1106, 1, 4, 99, 1101, 2, 3, 0, 99], &[]);
+        let larger = [
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99,
+        ];
+        for input in 6..=10 {
+            check(&[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8], &[input]);
+            check(&[3, 3, 1108, -1, 8, 3, 4, 3, 99], &[input]);
+            check(
+                &[3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9],
+                &[input],
+            );
+            check(&[3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1], &[input]);
+            check(&larger, &[input]);
+        }
+    }
+
+    #[test]
+    fn test_day09_examples() {
+        check(
+            &[
+                109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+            ],
+            &[],
+        );
+        check(&[1102, 34915192, 34915192, 7, 4, 7, 99, 0], &[]);
+        check(&[104, 1125899906842624, 99], &[]);
+        check(&[109, 5, 21101, 2, 3, 0, 99], &[]);
+        check(&[109, 7, 2201, 0, 1, 0, 99, 10, 20], &[]);
+    }
+
+    #[test]
+    fn test_errors() {
+        check(&[1101, 1, 1, 5, 42, 0], &[]);
+        check(&[301, 0, 0, 0, 99], &[]);
+        check(&[11101, 1, 1, 0, 99], &[]);
+        check(&[1, -1, 0, 0, 99], &[]);
+        check(&[109, -5, 204, 0, 99], &[]);
+        check(&[3, 0, 4, 0, 3, 0, 4, 0, 99], &[4]);
+        check(&[1105, 1, -3], &[]);
+        check(&[1101, i64::MAX, 1, 0, 99], &[]);
+        check(&[1102, i64::MAX, 2, 0, 99], &[]);
+        check(&[109, i64::MAX, 109, 1, 99], &[]);
+        check(&[109, i64::MAX, 204, 1, 99], &[]);
+    }
+
+    #[test]
+    fn test_falls_back_on_self_modification() {
+        // Outputs 7, then overwrites its own `out` with a halt and jumps back
+        // to it. Stale compiled code would loop forever.
+        let program = [104, 7, 1101, 99, 0, 0, 1105, 1, 0];
+        check(&program, &[]);
+        let mut memory = program.to_vec();
+        let mut output = vec![];
+        compute_with_io(&mut memory, &mut vec![], &mut output).unwrap();
+        assert_eq!(output, vec![7]);
+        assert_eq!(memory[0], 99);
+    }
+}
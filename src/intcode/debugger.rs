@@ -0,0 +1,511 @@
+//! An interactive debugger for intcode programs.
+//!
+//! `Debugger` wraps a `Machine` with breakpoints and understands a handful of
+//! text commands, so it can be driven from a REPL (see the `intcode-debug`
+//! binary) or from tests.
+
+use super::watch::{Access, WatchHit, Watchpoint};
+use super::{read_instruction, IntcodeError, Machine, Status};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// How many instructions `list` shows when no count is given.
+const DEFAULT_LIST_LEN: usize = 10;
+
+/// How many cells `mem` shows when no count is given.
+const DEFAULT_MEM_LEN: usize = 8;
+
+/// The highest address `poke` will grow memory to reach, unless the machine
+/// has a memory limit of its own.
+const POKE_LIMIT: usize = 1 << 24;
+
+/// How many steps the debugger can undo.
+const HISTORY_LIMIT: usize = 100_000;
+
+pub const HELP: &str = "\
+commands:
+  s, step [n]           execute n instructions (default 1)
+  c, continue           run until a breakpoint, halt, input wait or error
+  bs, back [n]          undo n instructions (default 1)
+  lw, lastwrite <addr>  undo back to the last instruction which wrote addr
+  b, break <addr>       set a breakpoint
+  d, delete <addr>      remove a breakpoint
+  bl, breakpoints       list breakpoints
+  w, watch <addr> [r|w|rw] [v]
+                        stop when addr is read and/or written (default: w),
+                        optionally only when the value is v
+  uw, unwatch <addr>    remove watchpoints on addr
+  i, input <v>...       queue input values
+  r, regs               show the instruction pointer, relative base and input queue
+  x, mem <addr> [n]     show n memory cells starting at addr
+  p, poke <addr> <v>    write v to addr
+  l, list [addr] [n]    disassemble n instructions from addr (default: ip)
+  h, help               show this message
+  q, quit               exit";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Step(usize),
+    Continue,
+    Back(usize),
+    LastWrite(usize),
+    Break(usize),
+    Delete(usize),
+    Breakpoints,
+    Watch {
+        address: usize,
+        access: Access,
+        value: Option<i64>,
+    },
+    Unwatch(usize),
+    Input(Vec<i64>),
+    Registers,
+    Memory {
+        address: usize,
+        len: usize,
+    },
+    Poke {
+        address: usize,
+        value: i64,
+    },
+    List {
+        address: Option<usize>,
+        len: usize,
+    },
+    Help,
+    Quit,
+}
+
+fn arg<T: FromStr>(args: &[&str], idx: usize) -> Result<Option<T>, String> {
+    match args.get(idx) {
+        Some(s) => s
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("invalid argument `{}`", s)),
+        None => Ok(None),
+    }
+}
+
+fn required<T: FromStr>(args: &[&str], idx: usize) -> Result<T, String> {
+    arg(args, idx)?.ok_or_else(|| "missing argument".to_string())
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let (name, args) = match words.split_first() {
+            Some((name, args)) => (*name, args),
+            None => return Err("empty command".to_string()),
+        };
+        Ok(match name {
+            "s" | "step" => Command::Step(arg(args, 0)?.unwrap_or(1)),
+            "c" | "continue" => Command::Continue,
+            "bs" | "back" => Command::Back(arg(args, 0)?.unwrap_or(1)),
+            "lw" | "lastwrite" => Command::LastWrite(required(args, 0)?),
+            "b" | "break" => Command::Break(required(args, 0)?),
+            "d" | "delete" => Command::Delete(required(args, 0)?),
+            "bl" | "breakpoints" => Command::Breakpoints,
+            "w" | "watch" => Command::Watch {
+                address: required(args, 0)?,
+                access: match args.get(1) {
+                    None | Some(&"w") => Access::Write,
+                    Some(&"r") => Access::Read,
+                    Some(&"rw") => Access::Any,
+                    Some(other) => return Err(format!("invalid access `{}`", other)),
+                },
+                value: arg(args, 2)?,
+            },
+            "uw" | "unwatch" => Command::Unwatch(required(args, 0)?),
+            "i" | "input" => Command::Input(
+                (0..args.len())
+                    .map(|i| required(args, i))
+                    .collect::<Result<_, _>>()?,
+            ),
+            "r" | "regs" => Command::Registers,
+            "x" | "mem" => Command::Memory {
+                address: required(args, 0)?,
+                len: arg(args, 1)?.unwrap_or(DEFAULT_MEM_LEN),
+            },
+            "p" | "poke" => Command::Poke {
+                address: required(args, 0)?,
+                value: required(args, 1)?,
+            },
+            "l" | "list" => Command::List {
+                address: arg(args, 0)?,
+                len: arg(args, 1)?.unwrap_or(DEFAULT_LIST_LEN),
+            },
+            "h" | "help" => Command::Help,
+            "q" | "quit" => Command::Quit,
+            _ => return Err(format!("unknown command `{}`", name)),
+        })
+    }
+}
+
+/// What happened when the debugger tried to execute an instruction.
+enum Outcome {
+    Ran,
+    Output(i64),
+    Halted,
+    NeedsInput,
+    Failed(IntcodeError),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Ran => write!(f, "ok"),
+            Outcome::Output(value) => write!(f, "output: {}", value),
+            Outcome::Halted => write!(f, "halted"),
+            Outcome::NeedsInput => write!(f, "waiting for input (queue some with `input`)"),
+            Outcome::Failed(e) => write!(f, "error: {}", e),
+        }
+    }
+}
+
+/// A `Machine` plus the bookkeeping needed to debug it.
+pub struct Debugger {
+    machine: Machine,
+    breakpoints: BTreeSet<usize>,
+}
+
+impl Debugger {
+    pub fn new(mut machine: Machine) -> Self {
+        machine.enable_history(Some(HISTORY_LIMIT));
+        Debugger {
+            machine,
+            breakpoints: BTreeSet::new(),
+        }
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    /// Renders the instruction at `address`, or the raw value if it doesn't
+    /// decode.
+    fn describe(&self, address: usize) -> String {
+        let marker = if address == self.machine.ip() {
+            "=>"
+        } else if self.breakpoints.contains(&address) {
+            " *"
+        } else {
+            "  "
+        };
+        match read_instruction(address, self.machine.memory()) {
+            Ok(op) => format!("{} {:>5}: {}", marker, address, op),
+            Err(_) => format!(
+                "{} {:>5}: .data {}",
+                marker,
+                address,
+                self.machine.peek(address)
+            ),
+        }
+    }
+
+    /// Executes one instruction, also returning any watchpoints it hit.
+    fn step_once(&mut self) -> (Outcome, Vec<WatchHit>) {
+        match self.machine.step() {
+            Ok(step) => {
+                let outcome = match step.status {
+                    // watchpoint statuses only come out of `Machine::run`
+                    None | Some(Status::Watchpoint(_)) => Outcome::Ran,
+                    Some(Status::Output(value)) => Outcome::Output(value),
+                    Some(Status::Halted) => Outcome::Halted,
+                    Some(Status::NeedsInput) => Outcome::NeedsInput,
+                };
+                (outcome, step.watch_hits)
+            }
+            Err(e) => (Outcome::Failed(e), vec![]),
+        }
+    }
+
+    /// Runs a command, returning the text to show the user.
+    pub fn execute(&mut self, command: Command) -> String {
+        let mut out = vec![];
+        match command {
+            Command::Step(n) => {
+                for _ in 0..n {
+                    let line = self.describe(self.machine.ip());
+                    let (outcome, hits) = self.step_once();
+                    match outcome {
+                        Outcome::Ran => out.push(line),
+                        Outcome::Output(value) => {
+                            out.push(line);
+                            out.push(format!("output: {}", value));
+                        }
+                        Outcome::Halted => {
+                            out.push(line);
+                            out.push("halted".to_string());
+                            break;
+                        }
+                        outcome => {
+                            out.push(outcome.to_string());
+                            break;
+                        }
+                    }
+                    if !hits.is_empty() {
+                        out.extend(hits.iter().map(|hit| format!("watchpoint: {}", hit)));
+                        break;
+                    }
+                }
+            }
+            Command::Continue => loop {
+                // the first step always runs, even from a breakpoint, so
+                // repeated `continue`s make progress.
+                let (outcome, hits) = self.step_once();
+                match outcome {
+                    Outcome::Ran => {}
+                    Outcome::Output(value) => out.push(format!("output: {}", value)),
+                    outcome => {
+                        out.push(outcome.to_string());
+                        break;
+                    }
+                }
+                if !hits.is_empty() {
+                    out.extend(hits.iter().map(|hit| format!("watchpoint: {}", hit)));
+                    out.push(self.describe(self.machine.ip()));
+                    break;
+                }
+                if self.breakpoints.contains(&self.machine.ip()) {
+                    out.push(format!("breakpoint at {}", self.machine.ip()));
+                    out.push(self.describe(self.machine.ip()));
+                    break;
+                }
+            },
+            Command::Back(n) => {
+                let undone = self.machine.rewind(n);
+                if undone < n {
+                    out.push(format!("undid {} of {} steps (no more history)", undone, n));
+                }
+                out.push(self.describe(self.machine.ip()));
+            }
+            Command::LastWrite(address) => match self.machine.rewind_to_last_write(address) {
+                Some(undone) => {
+                    out.push(format!("undid {} steps", undone));
+                    out.push(self.describe(self.machine.ip()));
+                }
+                None => out.push(format!("no write to {} in history", address)),
+            },
+            Command::Break(address) => {
+                self.breakpoints.insert(address);
+                out.push(format!("breakpoint set at {}", address));
+            }
+            Command::Delete(address) => {
+                if self.breakpoints.remove(&address) {
+                    out.push(format!("breakpoint removed from {}", address));
+                } else {
+                    out.push(format!("no breakpoint at {}", address));
+                }
+            }
+            Command::Breakpoints => {
+                if self.breakpoints.is_empty() {
+                    out.push("no breakpoints".to_string());
+                }
+                for &address in &self.breakpoints {
+                    out.push(self.describe(address));
+                }
+            }
+            Command::Watch {
+                address,
+                access,
+                value,
+            } => {
+                let mut watchpoint = Watchpoint::new(address, access);
+                if let Some(value) = value {
+                    watchpoint = watchpoint.when(move |v| v == value);
+                }
+                self.machine.add_watchpoint(watchpoint);
+                out.push(format!("watching {} of {}", access, address));
+            }
+            Command::Unwatch(address) => {
+                if self.machine.remove_watchpoints(address) {
+                    out.push(format!("watchpoints removed from {}", address));
+                } else {
+                    out.push(format!("no watchpoints on {}", address));
+                }
+            }
+            Command::Input(values) => {
+                for value in values {
+                    self.machine.push_input(value);
+                }
+                out.push(format!("input queue: {:?}", self.machine.pending_input()));
+            }
+            Command::Registers => {
+                out.push(format!("ip: {}", self.machine.ip()));
+                out.push(format!("rb: {}", self.machine.relative_base()));
+                out.push(format!("input queue: {:?}", self.machine.pending_input()));
+            }
+            Command::Memory { address, len } => {
+                let end = address.saturating_add(len).min(self.machine.memory().len());
+                if address >= end && len > 0 {
+                    out.push(format!(
+                        "{} is past the end of memory ({} cells)",
+                        address,
+                        self.machine.memory().len()
+                    ));
+                }
+                for address in address..end {
+                    out.push(format!("{:>5}: {}", address, self.machine.peek(address)));
+                }
+            }
+            Command::Poke { address, value } => {
+                let limit = self.machine.limits().memory.unwrap_or(POKE_LIMIT);
+                if address >= limit {
+                    out.push(format!(
+                        "{} is beyond the memory limit of {}",
+                        address, limit
+                    ));
+                } else {
+                    self.machine.poke(address, value);
+                    out.push(format!("{:>5}: {}", address, value));
+                }
+            }
+            Command::List { address, len } => {
+                let mut address = address.unwrap_or_else(|| self.machine.ip());
+                for _ in 0..len {
+                    if address >= self.machine.memory().len() {
+                        break;
+                    }
+                    out.push(self.describe(address));
+                    address += read_instruction(address, self.machine.memory())
+                        .map(|op| op.width())
+                        .unwrap_or(1);
+                }
+            }
+            Command::Help => out.push(HELP.to_string()),
+            Command::Quit => {}
+        }
+        out.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod debugger_tests {
+    use super::{Command, Debugger};
+    use crate::intcode::limits::Limits;
+    use crate::intcode::Machine;
+
+    fn debugger(program: &[i64]) -> Debugger {
+        Debugger::new(Machine::new(program.to_vec()))
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!("s".parse(), Ok(Command::Step(1)));
+        assert_eq!("step 5".parse(), Ok(Command::Step(5)));
+        assert_eq!("b 12".parse(), Ok(Command::Break(12)));
+        assert_eq!("input 1 -2".parse(), Ok(Command::Input(vec![1, -2])));
+        assert_eq!("x 4".parse(), Ok(Command::Memory { address: 4, len: 8 }));
+        assert_eq!("back".parse(), Ok(Command::Back(1)));
+        assert_eq!("lw 3".parse(), Ok(Command::LastWrite(3)));
+        assert!("b".parse::<Command>().is_err());
+        assert!("frobnicate".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn test_step_and_registers() {
+        let mut dbg = debugger(&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+        assert_eq!(
+            dbg.execute(Command::Step(1)),
+            "=>     0: add [9], [10], [3]"
+        );
+        assert_eq!(
+            dbg.execute(Command::Registers),
+            "ip: 4\nrb: 0\ninput queue: []"
+        );
+        assert_eq!(
+            dbg.execute(Command::Memory { address: 3, len: 1 }),
+            "    3: 70"
+        );
+    }
+
+    #[test]
+    fn test_breakpoint_and_continue() {
+        let mut dbg = debugger(&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+        dbg.execute(Command::Break(4));
+        assert_eq!(
+            dbg.execute(Command::Continue),
+            "breakpoint at 4\n=>     4: mul [3], [11], [0]"
+        );
+        assert_eq!(dbg.execute(Command::Continue), "halted");
+        assert_eq!(dbg.machine().memory()[0], 3500);
+    }
+
+    #[test]
+    fn test_back_and_last_write() {
+        let mut dbg = debugger(&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+        dbg.execute(Command::Continue);
+        assert_eq!(
+            dbg.execute(Command::LastWrite(3)),
+            "undid 3 steps\n=>     0: add [9], [10], [3]"
+        );
+        assert_eq!(dbg.machine().memory()[3], 3);
+        dbg.execute(Command::Step(2));
+        assert_eq!(
+            dbg.execute(Command::Back(3)),
+            "undid 2 of 3 steps (no more history)\n=>     0: add [9], [10], [3]"
+        );
+        assert_eq!(
+            dbg.execute(Command::LastWrite(0)),
+            "no write to 0 in history"
+        );
+    }
+
+    #[test]
+    fn test_input_and_output() {
+        let mut dbg = debugger(&[3, 0, 4, 0, 99]);
+        assert_eq!(
+            dbg.execute(Command::Continue),
+            "waiting for input (queue some with `input`)"
+        );
+        dbg.execute(Command::Input(vec![7]));
+        assert_eq!(dbg.execute(Command::Continue), "output: 7\nhalted");
+    }
+
+    #[test]
+    fn test_watch() {
+        let mut dbg = debugger(&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+        assert_eq!(
+            "w 0".parse::<Command>().map(|c| dbg.execute(c)),
+            Ok("watching write of 0".to_string())
+        );
+        assert_eq!(
+            dbg.execute(Command::Continue),
+            "watchpoint: write of 0 at 4 (value 3500)\n=>     8: hlt"
+        );
+    }
+
+    #[test]
+    fn test_poke() {
+        let mut dbg = debugger(&[99]);
+        dbg.execute(Command::Poke {
+            address: 3,
+            value: 5,
+        });
+        assert_eq!(dbg.machine().memory(), &[99, 0, 0, 5]);
+
+        assert_eq!(
+            dbg.execute("p 1000000000000 1".parse().unwrap()),
+            "1000000000000 is beyond the memory limit of 16777216"
+        );
+        dbg.machine.set_limits(Limits::default().memory(4));
+        assert_eq!(
+            dbg.execute("p 4 1".parse().unwrap()),
+            "4 is beyond the memory limit of 4"
+        );
+        assert_eq!(dbg.machine().memory(), &[99, 0, 0, 5]);
+    }
+
+    #[test]
+    fn test_memory() {
+        let mut dbg = debugger(&[1, 2, 3]);
+        assert_eq!(dbg.execute("x 1 8".parse().unwrap()), "    1: 2\n    2: 3");
+        assert_eq!(
+            dbg.execute("x 18446744073709551615 8".parse().unwrap()),
+            "18446744073709551615 is past the end of memory (3 cells)"
+        );
+    }
+}
@@ -0,0 +1,236 @@
+//! Turns intcode programs back into a readable listing.
+//!
+//! Each line shows an address followed by either a decoded instruction or a
+//! `.data` directive holding values that don't decode:
+//!
+//! ```text
+//!     0: add [9], [10], [3]
+//!     4: mul [3], [11], [0]
+//!     8: hlt
+//!     9: .data 30, 40, 50
+//! ```
+//!
+//! Params are rendered by mode: `[12]` is position mode, `#5` is immediate
+//! mode and `rb+3` is relative mode.
+
+use super::{Instructions, Op, Param};
+use std::fmt;
+
+/// The most values a single `.data` line will hold before wrapping.
+const DATA_PER_LINE: usize = 8;
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Param::Position(address) => write!(f, "[{}]", address),
+            Param::Immediate(value) => write!(f, "#{}", value),
+            Param::Relative(offset) if *offset < 0 => write!(f, "rb{}", offset),
+            Param::Relative(offset) => write!(f, "rb+{}", offset),
+        }
+    }
+}
+
+impl Op {
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Op::Add { .. } => "add",
+            Op::Multiply { .. } => "mul",
+            Op::Input { .. } => "in",
+            Op::Output { .. } => "out",
+            Op::JumpIfTrue { .. } => "jt",
+            Op::JumpIfFalse { .. } => "jf",
+            Op::LessThan { .. } => "lt",
+            Op::Equals { .. } => "eq",
+            Op::AdjustRelativeBase { .. } => "arb",
+            Op::Halt => "hlt",
+        }
+    }
+
+    /// The header which encodes this instruction in the standard way: the
+    /// opcode, plus a mode digit for each param and none past the last.
+    pub fn header(&self) -> i64 {
+        let opcode = match self {
+            Op::Add { .. } => 1,
+            Op::Multiply { .. } => 2,
+            Op::Input { .. } => 3,
+            Op::Output { .. } => 4,
+            Op::JumpIfTrue { .. } => 5,
+            Op::JumpIfFalse { .. } => 6,
+            Op::LessThan { .. } => 7,
+            Op::Equals { .. } => 8,
+            Op::AdjustRelativeBase { .. } => 9,
+            Op::Halt => 99,
+        };
+        let params = self.params();
+        let modes = params
+            .iter()
+            .zip(&[100, 1_000, 10_000])
+            .map(|(param, scale)| {
+                let mode = match param {
+                    Param::Position(_) => 0,
+                    Param::Immediate(_) => 1,
+                    Param::Relative(_) => 2,
+                };
+                mode * scale
+            });
+        opcode + modes.sum::<i64>()
+    }
+
+    /// The instruction's params, in the order they appear in memory.
+    pub fn params(&self) -> Vec<Param> {
+        match *self {
+            Op::Add { a, b, out }
+            | Op::Multiply { a, b, out }
+            | Op::LessThan { a, b, out }
+            | Op::Equals { a, b, out } => vec![a, b, out],
+            Op::JumpIfTrue { cond, target } | Op::JumpIfFalse { cond, target } => {
+                vec![cond, target]
+            }
+            Op::Input { out } => vec![out],
+            Op::Output { value } | Op::AdjustRelativeBase { value } => vec![value],
+            Op::Halt => vec![],
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mnemonic())?;
+        for (i, param) in self.params().iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { " " } else { ", " }, param)?;
+        }
+        Ok(())
+    }
+}
+
+/// A single line of a listing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Line {
+    Instruction { address: usize, op: Op },
+    Data { address: usize, values: Vec<i64> },
+}
+
+impl Line {
+    pub fn address(&self) -> usize {
+        match self {
+            Line::Instruction { address, .. } | Line::Data { address, .. } => *address,
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Line::Instruction { address, op } => write!(f, "{:>5}: {}", address, op),
+            Line::Data { address, values } => {
+                let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                write!(f, "{:>5}: .data {}", address, values.join(", "))
+            }
+        }
+    }
+}
+
+/// Decodes `program` from the start, falling back to `.data` for values which
+/// aren't valid instructions or whose params would run off the end.
+///
+/// Headers which decode but aren't written the standard way, like `1104`
+/// (an `out` with a mode digit for a second param it doesn't have), are data
+/// too, so that assembling the listing gives back the same program.
+pub fn disassemble(program: &[i64]) -> Vec<Line> {
+    let mut lines = vec![];
+    for (address, op) in Instructions::new(program) {
+        match op {
+            Ok(op) if address + op.width() <= program.len() && op.header() == program[address] => {
+                lines.push(Line::Instruction { address, op });
+            }
+            _ => {
+                let width = op
+                    .map(|op| op.width())
+                    .unwrap_or(1)
+                    .min(program.len() - address);
+                for (i, value) in program[address..address + width].iter().enumerate() {
+                    push_data(&mut lines, address + i, *value);
+                }
+            }
+        }
+    }
+    lines
+}
+
+/// Appends a data value to the listing, joining it onto the previous line
+/// when that line is data with room to spare.
+fn push_data(lines: &mut Vec<Line>, address: usize, value: i64) {
+    if let Some(Line::Data { values, .. }) = lines.last_mut() {
+        if values.len() < DATA_PER_LINE {
+            values.push(value);
+            return;
+        }
+    }
+    lines.push(Line::Data {
+        address,
+        values: vec![value],
+    });
+}
+
+/// Renders the full listing for `program`, one line per instruction.
+pub fn listing(program: &[i64]) -> String {
+    disassemble(program)
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+#[cfg(test)]
+mod disasm_tests {
+    use super::{disassemble, listing, Line};
+    use crate::intcode::{Op, Param};
+
+    #[test]
+    fn test_day02_example() {
+        assert_eq!(
+            listing(&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]),
+            "    0: add [9], [10], [3]\n    \
+             4: mul [3], [11], [0]\n    \
+             8: hlt\n    \
+             9: .data 30, 40, 50\n"
+        );
+    }
+
+    #[test]
+    fn test_param_modes() {
+        assert_eq!(
+            listing(&[21101, 5, -2, 3, 204, -1, 109, 19]),
+            "    0: add #5, #-2, rb+3\n    \
+             4: out rb-1\n    \
+             6: arb #19\n"
+        );
+    }
+
+    #[test]
+    fn test_nonstandard_header_is_data() {
+        assert_eq!(
+            listing(&[1104, 5, 199, 10105, 1, 0, 104, 1]),
+            "    0: .data 1104, 5, 199, 10105, 1, 0\n    \
+             6: out #1\n"
+        );
+    }
+
+    #[test]
+    fn test_truncated_instruction_is_data() {
+        assert_eq!(
+            disassemble(&[104, 7, 1, 0]),
+            vec![
+                Line::Instruction {
+                    address: 0,
+                    op: Op::Output {
+                        value: Param::Immediate(7)
+                    }
+                },
+                Line::Data {
+                    address: 2,
+                    values: vec![1, 0]
+                },
+            ]
+        );
+    }
+}
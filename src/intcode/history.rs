@@ -0,0 +1,279 @@
+//! Reverse execution for the `Machine`.
+//!
+//! With history enabled, every executed instruction leaves behind an undo
+//! record holding the registers and memory cells it changed. The machine can
+//! then be stepped backwards one instruction at a time, or rewound to just
+//! before the last write to a given address - useful for finding out which
+//! instruction put a surprising value into a self-modifying program.
+//!
+//! Stepping back also takes the step out of the profile, if one is being
+//! kept, and out of self-modification tracking, so code which only ran on the
+//! undone timeline isn't treated as executed.
+
+use super::selfmod::GuardUndo;
+use super::{Machine, MemoryWrite, Op, Status, Step};
+use std::collections::VecDeque;
+
+/// Everything needed to reverse a single step.
+#[derive(Debug, Clone)]
+struct UndoRecord {
+    ip: usize,
+    /// The executed instruction and where it left the ip.
+    op: Op,
+    next_ip: usize,
+    relative_base: i64,
+    memory_len: usize,
+    writes: Vec<MemoryWrite>,
+    /// An input value consumed by the step, which goes back on the queue.
+    input: Option<i64>,
+    /// Whether the step output a value.
+    output: bool,
+    /// Whether that value went into the output buffer.
+    buffered: bool,
+    /// What the step changed in self-modification tracking, if it was on.
+    guarded: Option<GuardUndo>,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct History {
+    records: VecDeque<UndoRecord>,
+    /// The most records to keep. The oldest are dropped past this point.
+    limit: Option<usize>,
+}
+
+impl History {
+    /// Remembers how to undo `step`, given the registers and memory size from
+    /// before it ran, whether output was being buffered, and what it changed
+    /// in self-modification tracking.
+    pub(super) fn push(
+        &mut self,
+        ip: usize,
+        relative_base: i64,
+        memory_len: usize,
+        buffered: bool,
+        guarded: Option<GuardUndo>,
+        step: &Step,
+    ) {
+        if self.limit == Some(0) {
+            return;
+        }
+        if Some(self.records.len()) == self.limit {
+            self.records.pop_front();
+        }
+        self.records.push_back(UndoRecord {
+            ip,
+            op: step.op,
+            next_ip: step.ip,
+            relative_base,
+            memory_len,
+            writes: step.writes.clone(),
+            input: match step.op {
+                Op::Input { .. } => step.writes.first().map(|w| w.new),
+                _ => None,
+            },
+            output: matches!(step.status, Some(Status::Output(_))),
+            buffered,
+            guarded,
+        });
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.records.clear();
+    }
+}
+
+impl Machine {
+    /// Starts keeping undo records for every executed instruction, holding on
+    /// to at most `limit` of them if given. Any existing history is cleared.
+    pub fn enable_history(&mut self, limit: Option<usize>) {
+        self.history = Some(History {
+            records: VecDeque::new(),
+            limit,
+        });
+    }
+
+    pub fn disable_history(&mut self) {
+        self.history = None;
+    }
+
+    /// The number of steps which can currently be undone.
+    pub fn history_len(&self) -> usize {
+        self.history.as_ref().map_or(0, |h| h.records.len())
+    }
+
+    /// Undoes the most recent step, returning the undone step's address, or
+    /// `None` if there's no history to undo.
+    pub fn step_back(&mut self) -> Option<usize> {
+        let record = self.history.as_mut()?.records.pop_back()?;
+        for write in record.writes.iter().rev() {
+            // memory only shrinks back to its old size below, so a missing
+            // cell was never there before the step either
+            if let Some(cell) = self.memory.get_mut(write.address) {
+                *cell = write.old;
+                self.cache.invalidate(write.address);
+            }
+        }
+        self.memory.truncate(record.memory_len);
+        self.ip = record.ip;
+        self.relative_base = record.relative_base;
+        if let Some(value) = record.input {
+            self.input.push_front(value);
+        }
+        if record.output {
+            self.outputs = self.outputs.saturating_sub(1);
+            if record.buffered {
+                self.output.pop();
+            }
+        }
+        self.steps = self.steps.saturating_sub(1);
+        if let Some(profile) = self.profile.as_mut() {
+            profile.unrecord(record.ip, &record.op, record.next_ip);
+        }
+        if let (Some(guard), Some(guarded)) = (self.code_guard.as_mut(), &record.guarded) {
+            guard.undo(guarded);
+        }
+        self.pending.clear();
+        self.halted = false;
+        Some(record.ip)
+    }
+
+    /// Undoes up to `n` steps, returning how many were actually undone.
+    pub fn rewind(&mut self, n: usize) -> usize {
+        (0..n).take_while(|_| self.step_back().is_some()).count()
+    }
+
+    /// Rewinds to just before the most recent write to `address`, leaving the
+    /// instruction pointer on the instruction which did the writing.
+    ///
+    /// Returns the number of steps undone, or `None` (having undone nothing)
+    /// if the history holds no write to `address`.
+    pub fn rewind_to_last_write(&mut self, address: usize) -> Option<usize> {
+        let records = &self.history.as_ref()?.records;
+        let back = records
+            .iter()
+            .rev()
+            .position(|r| r.writes.iter().any(|w| w.address == address))?;
+        Some(self.rewind(back + 1))
+    }
+}
+
+#[cfg(test)]
+mod history_tests {
+    use crate::intcode::selfmod::Mode;
+    use crate::intcode::{Machine, Status};
+
+    fn day02_example() -> Machine {
+        let mut machine = Machine::new(vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+        machine.enable_history(None);
+        machine
+    }
+
+    #[test]
+    fn test_step_back() {
+        let mut machine = day02_example();
+        assert_eq!(machine.run(), Ok(Status::Halted));
+        assert_eq!(machine.memory()[0], 3500);
+
+        assert_eq!(machine.step_back(), Some(8));
+        assert_eq!(machine.step_back(), Some(4));
+        assert_eq!(machine.memory()[0], 1);
+        assert_eq!(machine.memory()[3], 70);
+        assert_eq!(machine.step_back(), Some(0));
+        assert_eq!(
+            machine.memory(),
+            &[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]
+        );
+        assert_eq!(machine.step_back(), None);
+        assert_eq!(machine.steps(), 0);
+
+        assert_eq!(machine.run(), Ok(Status::Halted));
+        assert_eq!(machine.memory()[0], 3500);
+    }
+
+    #[test]
+    fn test_halt_recorded_once() {
+        let mut machine = day02_example();
+        machine.run().unwrap();
+        assert_eq!(machine.run(), Ok(Status::Halted));
+        assert_eq!(machine.step().unwrap().status, Some(Status::Halted));
+        assert_eq!(machine.history_len(), 3);
+        assert_eq!(machine.steps(), 3);
+
+        assert_eq!(machine.step_back(), Some(8));
+        assert_eq!(machine.run(), Ok(Status::Halted));
+        assert_eq!(machine.history_len(), 3);
+    }
+
+    #[test]
+    fn test_rewind_to_last_write() {
+        let mut machine = day02_example();
+        machine.run().unwrap();
+        assert_eq!(machine.rewind_to_last_write(3), Some(3));
+        assert_eq!(machine.ip(), 0);
+        assert_eq!(machine.rewind_to_last_write(0), None);
+    }
+
+    #[test]
+    fn test_undo_io_and_growth() {
+        let mut machine = Machine::new(vec![3, 10, 4, 10, 99]);
+        machine.enable_history(None);
+        machine.push_input(7);
+        assert_eq!(machine.run(), Ok(Status::Output(7)));
+        assert_eq!(machine.memory().len(), 11);
+
+        assert_eq!(machine.rewind(5), 2);
+        assert_eq!(machine.memory(), &[3, 10, 4, 10, 99]);
+        assert!(machine.output().is_empty());
+        assert_eq!(machine.pending_input(), &[7]);
+        assert_eq!(machine.run(), Ok(Status::Output(7)));
+    }
+
+    #[test]
+    fn test_undo_profile_and_code_tracking() {
+        // Jumps to `out #1` at 11 if the input is nonzero. Otherwise it writes
+        // over that `out`, which is only code if it ran.
+        let mut program = vec![3, 20, 1005, 20, 11, 1101, 0, 0, 12, 99, 0, 104, 1, 99];
+        program.resize(21, 0);
+        let mut machine = Machine::new(program);
+        machine.enable_history(None);
+        machine.enable_profiling();
+        machine.detect_self_modification(Mode::Reject);
+        machine.push_input(1);
+        assert_eq!(machine.run(), Ok(Status::Output(1)));
+
+        assert_eq!(machine.rewind(2), 2);
+        assert!(!machine.executed(11));
+        assert_eq!(machine.profile().unwrap().total(), 1);
+        assert_eq!(machine.profile().unwrap().count(11), 0);
+
+        machine.poke(20, 0);
+        assert_eq!(machine.run(), Ok(Status::Halted));
+        let profile = machine.profile().unwrap();
+        assert_eq!(
+            profile.opcodes(),
+            vec![("add", 1), ("hlt", 1), ("in", 1), ("jt", 1)]
+        );
+        assert!(profile.hot_loops().is_empty());
+
+        // Reported writes into code are taken back too.
+        let mut machine = day02_example();
+        machine.detect_self_modification(Mode::Report);
+        machine.run().unwrap();
+        assert_eq!(machine.code_writes().len(), 2);
+        assert_eq!(machine.rewind(2), 2);
+        assert_eq!(machine.code_writes().len(), 1);
+        assert!(!machine.executed(8));
+        assert_eq!(machine.run(), Ok(Status::Halted));
+        assert_eq!(machine.code_writes().len(), 2);
+    }
+
+    #[test]
+    fn test_limit() {
+        let mut machine = day02_example();
+        machine.enable_history(Some(1));
+        machine.run().unwrap();
+        assert_eq!(machine.history_len(), 1);
+        assert_eq!(machine.rewind(3), 1);
+        assert_eq!(machine.ip(), 8);
+    }
+}
@@ -0,0 +1,129 @@
+//! Resource limits for running untrusted or buggy programs.
+//!
+//! Nothing stops an intcode program from looping forever, writing to an
+//! address in the trillions (which the `Machine` would happily try to
+//! allocate), or producing output without end. `Limits` puts a cap on each,
+//! and a `Machine` which reaches one fails with a matching `IntcodeError`
+//! instead of going any further.
+
+use super::Machine;
+
+/// Caps on what a program may do. `None` means no cap, which is the default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// The most instructions to execute, counted by `Machine::steps`.
+    pub steps: Option<u64>,
+    /// The most memory cells a program may use. Writes at or beyond this
+    /// address fail.
+    pub memory: Option<usize>,
+    /// The most values a program may output.
+    pub outputs: Option<usize>,
+}
+
+impl Limits {
+    pub fn steps(mut self, limit: u64) -> Self {
+        self.steps = Some(limit);
+        self
+    }
+
+    pub fn memory(mut self, limit: usize) -> Self {
+        self.memory = Some(limit);
+        self
+    }
+
+    pub fn outputs(mut self, limit: usize) -> Self {
+        self.outputs = Some(limit);
+        self
+    }
+}
+
+impl Machine {
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+}
+
+#[cfg(test)]
+mod limits_tests {
+    use super::Limits;
+    use crate::intcode::{IntcodeError, Machine, Status};
+
+    #[test]
+    fn test_steps() {
+        let mut machine = Machine::new(vec![1105, 1, 0]);
+        machine.set_limits(Limits::default().steps(100));
+        assert_eq!(machine.run(), Err(IntcodeError::StepLimit { limit: 100 }));
+        assert_eq!(machine.steps(), 100);
+    }
+
+    #[test]
+    fn test_halt_at_step_limit() {
+        let mut machine = Machine::new(vec![104, 7, 99]);
+        machine.set_limits(Limits::default().steps(2));
+        assert_eq!(machine.run(), Ok(Status::Output(7)));
+        assert_eq!(machine.run(), Ok(Status::Halted));
+        assert_eq!(machine.run(), Ok(Status::Halted));
+        assert_eq!(machine.steps(), 2);
+
+        // overwriting the halt means the next step is a new one
+        machine.poke(2, 104);
+        assert_eq!(machine.run(), Err(IntcodeError::StepLimit { limit: 2 }));
+    }
+
+    #[test]
+    fn test_memory() {
+        let mut machine = Machine::new(vec![1101, 1, 1, 1000, 1101, 1, 1, 10, 99]);
+        machine.set_limits(Limits::default().memory(1000));
+        assert_eq!(
+            machine.run(),
+            Err(IntcodeError::MemoryLimit {
+                address: 0,
+                target: 1000,
+                limit: 1000
+            })
+        );
+        assert_eq!(machine.memory().len(), 9);
+
+        machine.set_limits(Limits::default().memory(1001));
+        assert_eq!(machine.run(), Ok(Status::Halted));
+    }
+
+    #[test]
+    fn test_outputs() {
+        let mut machine = Machine::new(vec![104, 7, 1105, 1, 0]);
+        machine.set_limits(Limits::default().outputs(3));
+        let mut output = vec![];
+        assert_eq!(
+            machine.run_with_io(&mut vec![], &mut output),
+            Err(IntcodeError::OutputLimit {
+                address: 0,
+                limit: 3
+            })
+        );
+        assert_eq!(output, vec![7, 7, 7]);
+    }
+
+    #[test]
+    fn test_outputs_survive_restore() {
+        let mut machine = Machine::new(vec![104, 7, 1105, 1, 0]);
+        machine.set_limits(Limits::default().outputs(2));
+        assert_eq!(machine.run(), Ok(Status::Output(7)));
+        assert_eq!(machine.take_output(), &[7]);
+
+        let snapshot = machine.snapshot();
+        machine.restore(&snapshot);
+        assert_eq!(machine.run(), Ok(Status::Output(7)));
+        assert!(machine.run().is_err());
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut machine = Machine::new(vec![1101, i64::MAX, 1, 0, 99]);
+        machine.set_limits(Limits::default().steps(100));
+        assert_eq!(machine.run(), Err(IntcodeError::Overflow { address: 0 }));
+    }
+}
@@ -0,0 +1,205 @@
+//! Reading intcode programs from text.
+//!
+//! Programs are comma separated integers. Whitespace, including line breaks,
+//! may appear around any value, so a long program can be split over several
+//! lines as long as each line but the last ends with a comma. A single
+//! trailing comma is allowed too.
+//!
+//! Comments are off by default, so that a stray `#` is reported rather than
+//! quietly cutting a line short. With `ParseOptions::comments` turned on,
+//! anything after a `#` on a line is a comment:
+//!
+//! ```text
+//! # add the first two values
+//! 1, 9, 10, 3,
+//! 2, 3, 11, 0,  # then multiply
+//! 99,
+//! 30, 40, 50
+//! ```
+//!
+//! Anything else, like a missing value between two commas, or two values with
+//! no comma between them, is an error which points at the offending spot.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Where the problem is, counting from 1.
+    pub line: usize,
+    pub column: usize,
+    /// The text which failed to parse, empty if a value was missing.
+    pub token: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.token.is_empty() {
+            write!(
+                f,
+                "expected a value at line {}, column {}",
+                self.line, self.column
+            )
+        } else {
+            write!(
+                f,
+                "invalid value `{}` at line {}, column {}",
+                self.token, self.line, self.column
+            )
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// How lenient `parse_program_with` is. The default is as strict as
+/// `parse_program`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Whether `#` starts a comment running to the end of the line.
+    pub comments: bool,
+}
+
+impl ParseOptions {
+    pub fn comments(mut self, comments: bool) -> Self {
+        self.comments = comments;
+        self
+    }
+}
+
+/// A value as written, before it's parsed.
+struct Token {
+    line: usize,
+    column: usize,
+    text: String,
+    /// Whether whitespace has been seen since the last character of `text`.
+    gap: bool,
+}
+
+impl Token {
+    fn new(line: usize, column: usize) -> Self {
+        Token {
+            line,
+            column,
+            text: String::new(),
+            gap: false,
+        }
+    }
+}
+
+/// Parses a program, reporting the first malformed value.
+pub fn parse_program(source: &str) -> Result<Vec<i64>, ParseError> {
+    parse_program_with(source, ParseOptions::default())
+}
+
+/// Parses a program like `parse_program`, with the leniency in `options`.
+pub fn parse_program_with(source: &str, options: ParseOptions) -> Result<Vec<i64>, ParseError> {
+    let mut tokens = vec![];
+    let mut token = Token::new(1, 1);
+    for (line, text) in source.lines().enumerate() {
+        let code = match text.find('#') {
+            Some(start) if options.comments => &text[..start],
+            _ => text,
+        };
+        for (column, c) in code.chars().enumerate() {
+            let (line, column) = (line + 1, column + 1);
+            if c == ',' {
+                if token.text.is_empty() {
+                    // Point at the comma which should have had a value before it.
+                    token = Token::new(line, column);
+                }
+                tokens.push(std::mem::replace(&mut token, Token::new(line, column + 1)));
+            } else if c.is_whitespace() {
+                token.gap = true;
+            } else {
+                if token.text.is_empty() {
+                    token = Token::new(line, column);
+                } else if token.gap {
+                    token.text.push(' ');
+                }
+                token.gap = false;
+                token.text.push(c);
+            }
+        }
+        token.gap = true;
+    }
+
+    let last = token;
+    let mut program = Vec::with_capacity(tokens.len() + 1);
+    for token in tokens
+        .into_iter()
+        .chain(Some(last).filter(|t| !t.text.is_empty()))
+    {
+        match token.text.parse() {
+            Ok(value) => program.push(value),
+            Err(_) => {
+                return Err(ParseError {
+                    line: token.line,
+                    column: token.column,
+                    token: token.text,
+                })
+            }
+        }
+    }
+    Ok(program)
+}
+
+#[cfg(test)]
+mod parse_tests {
+    use super::{parse_program, parse_program_with, ParseError, ParseOptions};
+
+    fn error(line: usize, column: usize, token: &str) -> Result<Vec<i64>, ParseError> {
+        Err(ParseError {
+            line,
+            column,
+            token: token.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_single_line() {
+        assert_eq!(parse_program("1,0,0,3,99\n"), Ok(vec![1, 0, 0, 3, 99]));
+        assert_eq!(parse_program(" 1, -2 ,3 "), Ok(vec![1, -2, 3]));
+        assert_eq!(parse_program("1,2,\r\n"), Ok(vec![1, 2]));
+        assert_eq!(parse_program(""), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_comments_and_lines() {
+        let source = "# add the first two values\n\
+                      1, 9, 10, 3,\n\
+                      2, 3, 11, 0,  # then multiply\n\
+                      \n\
+                      99,\n\
+                      30, 40, 50\n";
+        let options = ParseOptions::default().comments(true);
+        assert_eq!(
+            parse_program_with(source, options),
+            Ok(vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50])
+        );
+        assert_eq!(
+            parse_program(source),
+            error(1, 1, "# add the first two values 1")
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(parse_program("1,x,3"), error(1, 3, "x"));
+        assert_eq!(parse_program("1,,3"), error(1, 3, ""));
+        assert_eq!(parse_program(",1"), error(1, 1, ""));
+        assert_eq!(parse_program("1,2\n3,4"), error(1, 3, "2 3"));
+        assert_eq!(parse_program("1,\n  2 3"), error(2, 3, "2 3"));
+        assert_eq!(parse_program("1,2,,"), error(1, 5, ""));
+        assert_eq!(parse_program("1,2#,3"), error(1, 3, "2#"));
+        assert_eq!(
+            parse_program("1,99999999999999999999")
+                .unwrap_err()
+                .to_string(),
+            "invalid value `99999999999999999999` at line 1, column 3"
+        );
+        assert_eq!(
+            parse_program("1,,3").unwrap_err().to_string(),
+            "expected a value at line 1, column 3"
+        );
+    }
+}
@@ -0,0 +1,241 @@
+//! Execution profiling for the `Machine`.
+//!
+//! A `Profile` counts how many times each opcode and each instruction address
+//! was executed, plus every backward jump taken, which is how loops show up in
+//! intcode. `report` ranks all of these, and `heatmap` lays the address counts
+//! over a disassembly of the program.
+
+use super::disasm::{disassemble, Line};
+use super::{Machine, Op, Step};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+
+/// How many rows each section of the report shows.
+const REPORT_ROWS: usize = 10;
+
+/// The width of the widest bar in a heatmap.
+const HEATMAP_WIDTH: usize = 20;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    total: u64,
+    opcodes: BTreeMap<&'static str, u64>,
+    addresses: BTreeMap<usize, u64>,
+    /// Taken jumps from an address back to an earlier (or the same) one,
+    /// keyed by `(from, to)`.
+    loops: BTreeMap<(usize, usize), u64>,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Profile::default()
+    }
+
+    /// Counts one executed instruction.
+    pub fn record(&mut self, step: &Step) {
+        self.total += 1;
+        *self.opcodes.entry(step.op.mnemonic()).or_insert(0) += 1;
+        *self.addresses.entry(step.address).or_insert(0) += 1;
+        if let Some(jump) = backward_jump(step.address, &step.op, step.ip) {
+            *self.loops.entry(jump).or_insert(0) += 1;
+        }
+    }
+
+    /// Takes back the count for an instruction `op` at `address` which left
+    /// the ip at `ip`, when the step is undone.
+    pub(super) fn unrecord(&mut self, address: usize, op: &Op, ip: usize) {
+        self.total = self.total.saturating_sub(1);
+        uncount(&mut self.opcodes, op.mnemonic());
+        uncount(&mut self.addresses, address);
+        if let Some(jump) = backward_jump(address, op, ip) {
+            uncount(&mut self.loops, jump);
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Execution counts per mnemonic, most executed first.
+    pub fn opcodes(&self) -> Vec<(&'static str, u64)> {
+        ranked(&self.opcodes)
+    }
+
+    /// Execution counts per instruction address, most executed first.
+    pub fn addresses(&self) -> Vec<(usize, u64)> {
+        ranked(&self.addresses)
+    }
+
+    /// How often each backward jump was taken, as `((from, to), count)`, most
+    /// taken first.
+    pub fn hot_loops(&self) -> Vec<((usize, usize), u64)> {
+        ranked(&self.loops)
+    }
+
+    /// How many times the instruction at `address` was executed.
+    pub fn count(&self, address: usize) -> u64 {
+        self.addresses.get(&address).copied().unwrap_or(0)
+    }
+
+    /// Renders a disassembly of `program` with each line prefixed by its
+    /// execution count and a bar scaled against the hottest line.
+    pub fn heatmap(&self, program: &[i64]) -> String {
+        let lines: Vec<(u64, Line)> = disassemble(program)
+            .into_iter()
+            .map(|line| {
+                let count = match &line {
+                    Line::Instruction { address, .. } => self.count(*address),
+                    Line::Data { address, values } => (*address..address + values.len())
+                        .map(|a| self.count(a))
+                        .sum(),
+                };
+                (count, line)
+            })
+            .collect();
+        let max = lines
+            .iter()
+            .map(|(count, _)| *count)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        lines
+            .iter()
+            .map(|(count, line)| {
+                let bar = ((count * HEATMAP_WIDTH as u64 + max - 1) / max) as usize;
+                format!(
+                    "{:>10} {:<width$} {}",
+                    count,
+                    "#".repeat(bar),
+                    line,
+                    width = HEATMAP_WIDTH
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// The `(from, to)` of a jump taken back to an earlier (or the same) address
+/// by the instruction `op` at `address`, which left the ip at `ip`.
+fn backward_jump(address: usize, op: &Op, ip: usize) -> Option<(usize, usize)> {
+    let jumped = match op {
+        Op::JumpIfTrue { .. } | Op::JumpIfFalse { .. } => ip != address + op.width(),
+        _ => false,
+    };
+    if jumped && ip <= address {
+        Some((address, ip))
+    } else {
+        None
+    }
+}
+
+/// Decrements a count, dropping it once it reaches zero so it doesn't show up
+/// in the rankings. A step counted before profiling started has nothing to
+/// take back.
+fn uncount<K: Ord>(counts: &mut BTreeMap<K, u64>, key: K) {
+    if let Some(count) = counts.get_mut(&key) {
+        *count -= 1;
+        if *count == 0 {
+            counts.remove(&key);
+        }
+    }
+}
+
+/// Sorts counts by descending count, then ascending key.
+fn ranked<K: Copy + Ord>(counts: &BTreeMap<K, u64>) -> Vec<(K, u64)> {
+    let mut ranked: Vec<(K, u64)> = counts.iter().map(|(&k, &v)| (k, v)).collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    ranked
+}
+
+impl Display for Profile {
+    /// A report of the total and the top entries of each count, sorted.
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let percent = |count: u64| 100.0 * count as f64 / self.total.max(1) as f64;
+        writeln!(f, "total steps: {}", self.total)?;
+        writeln!(f, "by opcode:")?;
+        for (mnemonic, count) in self.opcodes() {
+            writeln!(
+                f,
+                "  {:<5} {:>10} {:>6.2}%",
+                mnemonic,
+                count,
+                percent(count)
+            )?;
+        }
+        writeln!(f, "by address:")?;
+        for (address, count) in self.addresses().into_iter().take(REPORT_ROWS) {
+            writeln!(f, "  {:>5} {:>10} {:>6.2}%", address, count, percent(count))?;
+        }
+        write!(f, "hot loops:")?;
+        for ((from, to), count) in self.hot_loops().into_iter().take(REPORT_ROWS) {
+            write!(f, "\n  {:>5} -> {:<5} {:>10}", from, to, count)?;
+        }
+        Ok(())
+    }
+}
+
+impl Machine {
+    /// Starts counting executed instructions into a fresh `Profile`.
+    pub fn enable_profiling(&mut self) {
+        self.profile = Some(Profile::new());
+    }
+
+    /// The profile gathered so far, if profiling is enabled.
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Stops profiling, returning everything counted.
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.profile.take()
+    }
+}
+
+#[cfg(test)]
+mod profile_tests {
+    use crate::intcode::Machine;
+
+    /// Counts down from 3, outputting each value.
+    const COUNTDOWN: [i64; 13] = [4, 12, 1001, 12, -1, 12, 1005, 12, 0, 99, 0, 0, 3];
+
+    #[test]
+    fn test_counts() {
+        let mut machine = Machine::new(COUNTDOWN.to_vec());
+        machine.enable_profiling();
+        machine.run_with_io(&mut vec![], &mut vec![]).unwrap();
+        let profile = machine.take_profile().unwrap();
+        assert_eq!(profile.total(), 10);
+        assert_eq!(
+            profile.opcodes(),
+            vec![("add", 3), ("jt", 3), ("out", 3), ("hlt", 1)]
+        );
+        assert_eq!(profile.count(0), 3);
+        assert_eq!(profile.count(9), 1);
+        assert_eq!(profile.hot_loops(), vec![((6, 0), 2)]);
+        assert_eq!(machine.profile(), None);
+    }
+
+    #[test]
+    fn test_report_and_heatmap() {
+        let mut machine = Machine::new(COUNTDOWN.to_vec());
+        machine.enable_profiling();
+        machine.run_with_io(&mut vec![], &mut vec![]).unwrap();
+        let profile = machine.profile().unwrap();
+
+        let report = profile.to_string();
+        assert!(report.starts_with("total steps: 10\nby opcode:\n  add"));
+        assert!(report.ends_with("hot loops:\n      6 -> 0              2"));
+
+        let heatmap = profile.heatmap(&COUNTDOWN);
+        let lines: Vec<&str> = heatmap.lines().collect();
+        assert_eq!(
+            lines[0],
+            format!("{:>10} {:<20}     0: out [12]", 3, "#".repeat(20))
+        );
+        assert_eq!(
+            lines[3],
+            format!("{:>10} {:<20}     9: hlt", 1, "#".repeat(7))
+        );
+    }
+}
@@ -0,0 +1,301 @@
+//! A parallel search for the noun and verb which make a program produce a
+//! target value, as in Day 2 Part 2.
+//!
+//! Pairs are numbered in the order a simple nested loop would try them, nouns
+//! outermost, and handed out to worker threads in small chunks. Whenever a
+//! worker finds a match it records the match's number if it's lower than any
+//! found so far, and workers stop as soon as everything left to try is past
+//! that. Every pair before the best match is always tried, so the answer is
+//! the same one a sequential search would find, however many threads run.
+//!
+//! Before any of that, the search tries `symbolic::analyze`, which for
+//! programs like the gravity assist one skips the brute force entirely.
+
+use super::limits::Limits;
+use super::{symbolic, IterSource, Machine};
+use std::convert::TryFrom;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// How many pairs a worker takes at a time.
+const CHUNK: usize = 64;
+
+/// The limits each pair runs under unless told otherwise, so that a pair
+/// which never halts (or tries to allocate the world) is skipped rather than
+/// hanging the search.
+const DEFAULT_LIMITS: Limits = Limits {
+    steps: Some(1_000_000),
+    memory: Some(1 << 20),
+    outputs: Some(10_000),
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Search {
+    nouns: RangeInclusive<i64>,
+    verbs: RangeInclusive<i64>,
+    target_address: usize,
+    threads: usize,
+    analyze: bool,
+    limits: Limits,
+    decode_cache: bool,
+}
+
+/// Searches nouns and verbs from 0 to 99 for a result at address 0, with one
+/// thread per available core, trying symbolic analysis first and running each
+/// pair without the decode cache.
+impl Default for Search {
+    fn default() -> Self {
+        Search {
+            nouns: 0..=99,
+            verbs: 0..=99,
+            target_address: 0,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            analyze: true,
+            limits: DEFAULT_LIMITS,
+            decode_cache: false,
+        }
+    }
+}
+
+impl Search {
+    pub fn new() -> Self {
+        Search::default()
+    }
+
+    /// The nouns to try, written to address 1.
+    pub fn nouns(mut self, nouns: RangeInclusive<i64>) -> Self {
+        self.nouns = nouns;
+        self
+    }
+
+    /// The verbs to try, written to address 2.
+    pub fn verbs(mut self, verbs: RangeInclusive<i64>) -> Self {
+        self.verbs = verbs;
+        self
+    }
+
+    /// The address compared against the target once the program halts.
+    pub fn target_address(mut self, address: usize) -> Self {
+        self.target_address = address;
+        self
+    }
+
+    /// The number of worker threads. Zero is treated as one.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Whether to try solving for the target with `symbolic::analyze` before
+    /// resorting to brute force. This only applies when neither range goes
+    /// below zero, since a negative noun or verb used as an address makes the
+    /// program fail where the analysis wouldn't.
+    pub fn analyze(mut self, analyze: bool) -> Self {
+        self.analyze = analyze;
+        self
+    }
+
+    /// The limits each pair is run under. Pairs which hit one are skipped.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Whether each pair runs with the decoded-instruction cache. It's off by
+    /// default: Day 2 style programs run straight through without repeating
+    /// an instruction, so the cache only costs time. It's worth turning on
+    /// for programs which loop. See `Machine::set_decode_cache`.
+    pub fn decode_cache(mut self, enabled: bool) -> Self {
+        self.decode_cache = enabled;
+        self
+    }
+
+    /// The pair numbered `index`. Offsets are always within their range, so
+    /// wrapping arithmetic lands on the right value even when the offset is
+    /// too big for an `i64`.
+    fn pair(&self, index: usize) -> (i64, i64) {
+        let width = range_len(&self.verbs);
+        (
+            self.nouns.start().wrapping_add((index / width) as i64),
+            self.verbs.start().wrapping_add((index % width) as i64),
+        )
+    }
+
+    /// Finds the first noun and verb, in order, which leave `target` at the
+    /// target address. Pairs which cause the program to fail are skipped.
+    ///
+    /// If the target address ends up as a linear function of the noun and
+    /// verb, the answer is solved for directly and checked by running it.
+    /// Otherwise every pair is run, including when solving finds no answer:
+    /// the analysis is only trusted as far as a pair it can check.
+    pub fn run(&self, target: i64, program: &[i64]) -> Option<(i64, i64)> {
+        if self.analyze && *self.nouns.start() >= 0 && *self.verbs.start() >= 0 {
+            if let Some(linear) = symbolic::analyze(program, self.target_address, self.limits) {
+                if let Some((noun, verb)) = linear.solve(target, &self.nouns, &self.verbs) {
+                    if self.check(target, program, noun, verb) {
+                        return Some((noun, verb));
+                    }
+                }
+            }
+        }
+        self.brute_force(target, program)
+    }
+
+    fn brute_force(&self, target: i64, program: &[i64]) -> Option<(i64, i64)> {
+        let total = range_len(&self.nouns).saturating_mul(range_len(&self.verbs));
+        let next_chunk = AtomicUsize::new(0);
+        let best = AtomicUsize::new(usize::MAX);
+
+        let worker = || loop {
+            let start = next_chunk.fetch_add(CHUNK, Ordering::Relaxed);
+            if start >= total || start >= best.load(Ordering::Relaxed) {
+                return;
+            }
+            for index in start..start.saturating_add(CHUNK).min(total) {
+                if index >= best.load(Ordering::Relaxed) {
+                    return;
+                }
+                let (noun, verb) = self.pair(index);
+                if self.check(target, program, noun, verb) {
+                    best.fetch_min(index, Ordering::Relaxed);
+                    return;
+                }
+            }
+        };
+        thread::scope(|scope| {
+            for _ in 0..self.threads.max(1) {
+                scope.spawn(worker);
+            }
+        });
+
+        match best.into_inner() {
+            usize::MAX => None,
+            index => Some(self.pair(index)),
+        }
+    }
+
+    fn check(&self, target: i64, program: &[i64], noun: i64, verb: i64) -> bool {
+        let mut machine = Machine::new(program.to_vec());
+        machine.poke(1, noun);
+        machine.poke(2, verb);
+        machine.set_limits(self.limits);
+        machine.set_decode_cache(self.decode_cache);
+        let result = machine.run_with_io(&mut IterSource(std::iter::empty()), &mut vec![]);
+        result.is_ok() && machine.peek(self.target_address) == target
+    }
+}
+
+/// The number of values in `range`, saturating at `usize::MAX` for ranges
+/// too wide to ever finish searching anyway.
+fn range_len(range: &RangeInclusive<i64>) -> usize {
+    if range.is_empty() {
+        0
+    } else {
+        let span = range.end().wrapping_sub(*range.start()) as u64;
+        usize::try_from(span).map_or(usize::MAX, |span| span.saturating_add(1))
+    }
+}
+
+#[cfg(test)]
+mod search_tests {
+    use super::Search;
+    use crate::intcode::compute_with_io;
+    use crate::intcode::limits::Limits;
+
+    /// Leaves `[noun] + [verb]` at address 0 and `noun * verb` at address 3.
+    /// Past the code, each cell holds its own address.
+    fn program() -> Vec<i64> {
+        let mut program = vec![1, 0, 0, 0, 2, 1, 2, 3, 99];
+        program.extend(9..100);
+        program
+    }
+
+    /// The straightforward nested loop the search has to agree with.
+    fn sequential(target: i64, address: usize) -> Option<(i64, i64)> {
+        (0..=99)
+            .flat_map(|noun| (0..=99).map(move |verb| (noun, verb)))
+            .find(|&(noun, verb)| {
+                let mut memory = program();
+                memory[1] = noun;
+                memory[2] = verb;
+                compute_with_io(&mut memory, &mut vec![], &mut vec![]).is_ok()
+                    && memory[address] == target
+            })
+    }
+
+    #[test]
+    fn test_matches_sequential_order() {
+        for &threads in &[1, 2, 3, 8] {
+            let search = Search::new().threads(threads);
+            for &target in &[4, 150, 198, 1000] {
+                assert_eq!(search.run(target, &program()), sequential(target, 0));
+            }
+            assert_eq!(search.run(150, &program()), Some((2, 75)));
+            assert_eq!(search.run(1000, &program()), None);
+        }
+    }
+
+    #[test]
+    fn test_linear_shortcut() {
+        // 250000 * noun + verb + 12345, as in `symbolic_tests`.
+        let mut program = vec![
+            1, 0, 0, 3, 2, 1, 20, 19, 1, 19, 2, 19, 1001, 19, 12345, 0, 99, 0, 0, 0, 250000,
+        ];
+        program.resize(100, 0);
+        for &target in &[3012379, 12345, 12344, 250000 * 99 + 12345 + 99] {
+            let analyzed = Search::new().run(target, &program);
+            assert_eq!(analyzed, Search::new().analyze(false).run(target, &program));
+        }
+        assert_eq!(Search::new().run(3012379, &program), Some((12, 34)));
+        let search = Search::new().nouns(20..=30);
+        assert_eq!(search.run(3012379, &program), None);
+        assert_eq!(Search::new().run(i64::MIN, &program), None);
+    }
+
+    #[test]
+    fn test_skips_pairs_which_never_halt() {
+        // `jf noun, verb`: noun 0 and verb 0 loops forever, and verb 3 halts.
+        let program = [1106, 0, 0, 99];
+        let search = Search::new().limits(Limits::default().steps(1000));
+        assert_eq!(search.run(1106, &program), Some((0, 3)));
+    }
+
+    #[test]
+    fn test_short_program() {
+        // Addresses 1 and 2 are past the end, but get written all the same.
+        assert_eq!(Search::new().run(99, &[99]), Some((0, 0)));
+        assert_eq!(Search::new().run(0, &[99]), None);
+        assert_eq!(Search::new().target_address(2).run(7, &[]), None);
+    }
+
+    #[test]
+    fn test_wide_ranges() {
+        let program = [99, 0, 0];
+        let all = i64::MIN..=i64::MAX;
+        let search = Search::new().nouns(all.clone()).verbs(all.clone());
+        assert_eq!(
+            search.target_address(2).run(i64::MIN + 3, &program),
+            Some((i64::MIN, i64::MIN + 3))
+        );
+        let search = Search::new().nouns(all).verbs(i64::MIN..=i64::MIN + 9);
+        assert_eq!(
+            search.target_address(1).run(i64::MIN + 1, &program),
+            Some((i64::MIN + 1, i64::MIN))
+        );
+    }
+
+    #[test]
+    fn test_ranges_and_target_address() {
+        let search = Search::new().nouns(60..=70).verbs(10..=20).threads(4);
+        assert_eq!(search.run(80, &program()), Some((60, 20)));
+        assert_eq!(search.run(150, &program()), None);
+
+        let search = search.target_address(3);
+        assert_eq!(search.run(700, &program()), Some((70, 10)));
+        assert_eq!(
+            Search::new().target_address(3).run(700, &program()),
+            sequential(700, 3)
+        );
+    }
+}
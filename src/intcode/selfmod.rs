@@ -0,0 +1,226 @@
+//! Detection of self-modifying code.
+//!
+//! Plenty of intcode programs write into their own instructions - the Day 2
+//! gravity assist program stores every result over one of its own params.
+//! That's legal, but it's also an easy way for a buggy program (or a buggy
+//! interpreter) to go wrong, so the `Machine` can keep track of which
+//! addresses have been executed and flag writes which land on them, or on the
+//! instruction about to run next.
+//!
+//! In `Mode::Report` the writes go ahead and are collected for inspection with
+//! `Machine::code_writes`. In `Mode::Reject` the first such write fails with
+//! `IntcodeError::SelfModifying` before memory is touched.
+
+use super::{read_instruction, Machine};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Report,
+    Reject,
+}
+
+/// What kind of code a write landed on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// Part of an instruction which has already been executed, including the
+    /// one doing the writing.
+    Executed,
+    /// Part of the instruction which will run next.
+    Upcoming,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeWrite {
+    /// The address of the instruction doing the writing.
+    pub ip: usize,
+    pub target: usize,
+    pub kind: Target,
+}
+
+impl fmt::Display for CodeWrite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            Target::Executed => "executed",
+            Target::Upcoming => "upcoming",
+        };
+        write!(
+            f,
+            "instruction at {} wrote to {} code at {}",
+            self.ip, kind, self.target
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct CodeGuard {
+    pub(super) mode: Mode,
+    /// Which addresses have been part of an executed instruction.
+    executed: Vec<bool>,
+    writes: Vec<CodeWrite>,
+    /// How many of `writes` were made by steps which have been marked.
+    marked_writes: usize,
+}
+
+/// What a single step changed in a `CodeGuard`.
+#[derive(Debug, Clone, Default)]
+pub(super) struct GuardUndo {
+    /// The addresses first executed by the step.
+    marked: Vec<usize>,
+    /// How many writes into code the step made.
+    writes: usize,
+}
+
+impl CodeGuard {
+    /// Forgets everything executed and written so far.
+    pub(super) fn clear(&mut self) {
+        self.executed.clear();
+        self.writes.clear();
+        self.marked_writes = 0;
+    }
+
+    /// Notes that the `width` values from `ip` were executed, finishing a
+    /// step. Returns what the step changed, for undoing it.
+    pub(super) fn mark(&mut self, ip: usize, width: usize) -> GuardUndo {
+        if ip + width > self.executed.len() {
+            self.executed.resize(ip + width, false);
+        }
+        let mut marked = vec![];
+        for (address, cell) in (ip..).zip(&mut self.executed[ip..ip + width]) {
+            if !*cell {
+                *cell = true;
+                marked.push(address);
+            }
+        }
+        let writes = self.writes.len() - self.marked_writes;
+        self.marked_writes = self.writes.len();
+        GuardUndo { marked, writes }
+    }
+
+    /// Reverses the step `undo` came from, which must be the last one marked
+    /// that hasn't already been undone.
+    pub(super) fn undo(&mut self, undo: &GuardUndo) {
+        for &address in &undo.marked {
+            // tracking may have been restarted since, leaving less marked
+            if let Some(cell) = self.executed.get_mut(address) {
+                *cell = false;
+            }
+        }
+        self.marked_writes = self.marked_writes.saturating_sub(undo.writes);
+        self.writes.truncate(self.marked_writes);
+    }
+
+    /// Checks a write to `target` by the instruction at `ip`, which occupies
+    /// memory up to `next`, where the following instruction starts.
+    pub(super) fn check(
+        &mut self,
+        ip: usize,
+        next: usize,
+        target: usize,
+        memory: &[i64],
+    ) -> Option<CodeWrite> {
+        let kind = if (ip..next).contains(&target) || self.executed.get(target) == Some(&true) {
+            Target::Executed
+        } else {
+            let width = read_instruction(next, memory).map_or(1, |op| op.width());
+            if !(next..next + width).contains(&target) {
+                return None;
+            }
+            Target::Upcoming
+        };
+        let write = CodeWrite { ip, target, kind };
+        self.writes.push(write);
+        Some(write)
+    }
+}
+
+impl Machine {
+    /// Starts tracking executed addresses and checking writes against them.
+    /// Anything executed before this is called isn't counted.
+    pub fn detect_self_modification(&mut self, mode: Mode) {
+        self.code_guard = Some(CodeGuard {
+            mode,
+            executed: vec![],
+            writes: vec![],
+            marked_writes: 0,
+        });
+    }
+
+    /// Every write into code seen so far, oldest first.
+    pub fn code_writes(&self) -> &[CodeWrite] {
+        self.code_guard.as_ref().map_or(&[], |guard| &guard.writes)
+    }
+
+    /// Whether `address` has been part of an executed instruction since
+    /// detection was turned on.
+    pub fn executed(&self, address: usize) -> bool {
+        self.code_guard
+            .as_ref()
+            .map_or(false, |guard| guard.executed.get(address) == Some(&true))
+    }
+}
+
+#[cfg(test)]
+mod selfmod_tests {
+    use super::{CodeWrite, Mode, Target};
+    use crate::intcode::{IntcodeError, Machine, Status};
+
+    #[test]
+    fn test_report() {
+        // Day 2's example writes over the first instruction's out param, then
+        // over the first instruction's opcode.
+        let mut machine = Machine::new(vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+        machine.detect_self_modification(Mode::Report);
+        assert_eq!(machine.run(), Ok(Status::Halted));
+        assert_eq!(
+            machine.code_writes(),
+            &[
+                CodeWrite {
+                    ip: 0,
+                    target: 3,
+                    kind: Target::Executed
+                },
+                CodeWrite {
+                    ip: 4,
+                    target: 0,
+                    kind: Target::Executed
+                },
+            ]
+        );
+        assert!(machine.executed(8));
+        assert!(!machine.executed(9));
+    }
+
+    #[test]
+    fn test_upcoming() {
+        // Day 5: the multiply turns the following 33 into a halt.
+        let mut machine = Machine::new(vec![1002, 4, 3, 4, 33]);
+        machine.detect_self_modification(Mode::Report);
+        assert_eq!(machine.run(), Ok(Status::Halted));
+        assert_eq!(machine.code_writes()[0].kind, Target::Upcoming);
+        assert_eq!(
+            machine.code_writes()[0].to_string(),
+            "instruction at 0 wrote to upcoming code at 4"
+        );
+    }
+
+    #[test]
+    fn test_reject() {
+        let mut machine = Machine::new(vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+        machine.detect_self_modification(Mode::Reject);
+        assert_eq!(
+            machine.run(),
+            Err(IntcodeError::SelfModifying {
+                address: 0,
+                target: 3
+            })
+        );
+        assert_eq!(machine.memory()[3], 3);
+
+        // Writing to data is fine.
+        let mut machine = Machine::new(vec![1101, 1, 2, 5, 99, 0]);
+        machine.detect_self_modification(Mode::Reject);
+        assert_eq!(machine.run(), Ok(Status::Halted));
+        assert!(machine.code_writes().is_empty());
+    }
+}
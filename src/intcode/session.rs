@@ -0,0 +1,460 @@
+//! Recording and replaying the input and output of a program run.
+//!
+//! `record` runs a machine against any input source and output sink, noting
+//! each value that passes through along with the step at which it happened.
+//! `replay` runs a fresh machine against a recording, feeding it the same
+//! inputs and checking it produces the same outputs at the same points. This
+//! turns an interactive session into something which can be checked into a
+//! test.
+//!
+//! Sessions are saved as text, one event per line:
+//!
+//! ```text
+//! intcode-session 1
+//! in 0 8
+//! out 6 1000
+//! ```
+//!
+//! The first number is the index of the instruction which consumed or
+//! produced the value, as counted by `Machine::steps`.
+//!
+//! `record_to` writes each event to a `SessionWriter` the moment it happens,
+//! so a recording of a program which hangs or is killed isn't lost.
+
+use super::{InputSource, IntcodeError, Machine, OutputSink, Status};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+const HEADER: &str = "intcode-session 1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Input { step: u64, value: i64 },
+    Output { step: u64, value: i64 },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Input { step, value } => write!(f, "in {} {}", step, value),
+            Event::Output { step, value } => write!(f, "out {} {}", step, value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Session {
+    pub events: Vec<Event>,
+}
+
+#[derive(Debug)]
+pub enum SessionError {
+    Io(io::Error),
+    /// The file isn't a session, or is from an unknown version.
+    BadHeader,
+    /// The event on this line (counting from 1) couldn't be parsed.
+    BadEvent(usize),
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionError::Io(e) => write!(f, "{}", e),
+            SessionError::BadHeader => write!(f, "not an intcode session"),
+            SessionError::BadEvent(line) => write!(f, "line {}: invalid event", line),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+impl From<io::Error> for SessionError {
+    fn from(e: io::Error) -> Self {
+        SessionError::Io(e)
+    }
+}
+
+impl Session {
+    pub fn save(&self, mut out: impl Write) -> io::Result<()> {
+        writeln!(out, "{}", HEADER)?;
+        for event in &self.events {
+            writeln!(out, "{}", event)?;
+        }
+        out.flush()
+    }
+
+    pub fn load(input: impl Read) -> Result<Self, SessionError> {
+        let mut lines = BufReader::new(input).lines();
+        let header = lines.next().transpose()?;
+        if header.as_deref().map(str::trim_end) != Some(HEADER) {
+            return Err(SessionError::BadHeader);
+        }
+
+        let mut events = vec![];
+        for (i, line) in lines.enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let bad = || SessionError::BadEvent(i + 2);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let (step, value) = match words.as_slice() {
+                [_, step, value] => (
+                    step.parse().map_err(|_| bad())?,
+                    value.parse().map_err(|_| bad())?,
+                ),
+                _ => return Err(bad()),
+            };
+            events.push(match words[0] {
+                "in" => Event::Input { step, value },
+                "out" => Event::Output { step, value },
+                _ => return Err(bad()),
+            });
+        }
+        Ok(Session { events })
+    }
+}
+
+/// Writes a session out one event at a time, flushing after each so that
+/// everything recorded so far is saved whenever the process stops.
+///
+/// Write errors don't interrupt the recording. The first one is kept and
+/// reported by `finish`.
+pub struct SessionWriter {
+    out: Box<dyn Write>,
+    error: Option<io::Error>,
+}
+
+impl SessionWriter {
+    /// Starts a session on `out`, writing the header straight away.
+    pub fn new(out: impl Write + 'static) -> Self {
+        let mut writer = SessionWriter {
+            out: Box::new(out),
+            error: None,
+        };
+        writer.write_line(HEADER);
+        writer
+    }
+
+    /// Creates (or truncates) a session file at `path`.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(SessionWriter::new(File::create(path)?))
+    }
+
+    pub fn write(&mut self, event: &Event) {
+        self.write_line(event);
+    }
+
+    fn write_line(&mut self, line: impl fmt::Display) {
+        if self.error.is_some() {
+            return;
+        }
+        let result = writeln!(self.out, "{}", line).and_then(|_| self.out.flush());
+        if let Err(e) = result {
+            self.error = Some(e);
+        }
+    }
+
+    /// Reports the first error seen while writing the session.
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.out.flush(),
+        }
+    }
+}
+
+/// Runs `machine` until it halts, like `Machine::run_with_io`, keeping a
+/// record of every value read from `input` and sent to `output`.
+///
+/// The session is returned even if the run fails, since a recording of a
+/// crash is often the most useful kind. Values already queued on the machine
+/// before recording starts are not part of the session.
+pub fn record(
+    machine: &mut Machine,
+    input: &mut impl InputSource,
+    output: &mut impl OutputSink,
+) -> (Session, Result<(), IntcodeError>) {
+    let mut session = Session::default();
+    let result = record_events(machine, input, output, |event| session.events.push(event));
+    (session, result)
+}
+
+/// Like `record`, but writes each event to `writer` as soon as it happens
+/// rather than collecting them.
+pub fn record_to(
+    machine: &mut Machine,
+    input: &mut impl InputSource,
+    output: &mut impl OutputSink,
+    writer: &mut SessionWriter,
+) -> Result<(), IntcodeError> {
+    record_events(machine, input, output, |event| writer.write(&event))
+}
+
+fn record_events(
+    machine: &mut Machine,
+    input: &mut impl InputSource,
+    output: &mut impl OutputSink,
+    mut on_event: impl FnMut(Event),
+) -> Result<(), IntcodeError> {
+    machine.unbuffered(|machine| loop {
+        match machine.run() {
+            Ok(Status::Halted) => break Ok(()),
+            Ok(Status::NeedsInput) => match input.next_input() {
+                Some(value) => {
+                    on_event(Event::Input {
+                        step: machine.steps(),
+                        value,
+                    });
+                    machine.push_input(value);
+                }
+                None => {
+                    break Err(IntcodeError::InputExhausted {
+                        address: machine.ip(),
+                    })
+                }
+            },
+            Ok(Status::Output(value)) => {
+                on_event(Event::Output {
+                    step: machine.steps() - 1,
+                    value,
+                });
+                output.send_output(value);
+            }
+            Ok(Status::Watchpoint(_)) => {}
+            Err(e) => break Err(e),
+        }
+    })
+}
+
+/// The ways a replay can fail to match its recording.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    Intcode(IntcodeError),
+    /// The program asked for input at `step` when the recording expected
+    /// something else.
+    UnexpectedInput {
+        step: u64,
+        expected: Option<Event>,
+    },
+    /// The program produced an output the recording didn't expect.
+    UnexpectedOutput {
+        found: Event,
+        expected: Option<Event>,
+    },
+    /// The program halted at `step` before the recording was finished.
+    UnexpectedHalt {
+        step: u64,
+        expected: Event,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let expected = |e: &Option<Event>| match e {
+            Some(e) => format!("`{}`", e),
+            None => "the end of the session".to_string(),
+        };
+        match self {
+            ReplayError::Intcode(e) => write!(f, "{}", e),
+            ReplayError::UnexpectedInput { step, expected: e } => write!(
+                f,
+                "program asked for input at step {}, expected {}",
+                step,
+                expected(e)
+            ),
+            ReplayError::UnexpectedOutput { found, expected: e } => {
+                write!(f, "program produced `{}`, expected {}", found, expected(e))
+            }
+            ReplayError::UnexpectedHalt { step, expected: e } => {
+                write!(f, "program halted at step {}, expected `{}`", step, e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<IntcodeError> for ReplayError {
+    fn from(e: IntcodeError) -> Self {
+        ReplayError::Intcode(e)
+    }
+}
+
+/// Runs `machine` until it halts, feeding it the inputs from `session` and
+/// checking every input and output happens at the recorded step.
+pub fn replay(machine: &mut Machine, session: &Session) -> Result<(), ReplayError> {
+    machine.unbuffered(|machine| replay_events(machine, session))
+}
+
+fn replay_events(machine: &mut Machine, session: &Session) -> Result<(), ReplayError> {
+    let mut events = session.events.iter().copied();
+    let mut next = events.next();
+    loop {
+        match machine.run()? {
+            Status::Halted => {
+                return match next {
+                    Some(expected) => Err(ReplayError::UnexpectedHalt {
+                        step: machine.steps() - 1,
+                        expected,
+                    }),
+                    None => Ok(()),
+                }
+            }
+            Status::NeedsInput => {
+                let step = machine.steps();
+                match next {
+                    Some(Event::Input { step: s, value }) if s == step => machine.push_input(value),
+                    expected => return Err(ReplayError::UnexpectedInput { step, expected }),
+                }
+            }
+            Status::Output(value) => {
+                let found = Event::Output {
+                    step: machine.steps() - 1,
+                    value,
+                };
+                if next != Some(found) {
+                    return Err(ReplayError::UnexpectedOutput {
+                        found,
+                        expected: next,
+                    });
+                }
+            }
+            Status::Watchpoint(_) => continue,
+        }
+        next = events.next();
+    }
+}
+
+#[cfg(test)]
+mod session_tests {
+    use super::{record, record_to, replay, Event, ReplayError, Session, SessionWriter};
+    use crate::intcode::{IntcodeError, Machine};
+    use std::cell::RefCell;
+    use std::io::{self, Write};
+    use std::rc::Rc;
+
+    /// Outputs 999, 1000 or 1001 depending on whether its input is below,
+    /// equal to or above 8.
+    const COMPARE_TO_8: [i64; 47] = [
+        3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0, 0,
+        1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20,
+        1105, 1, 46, 98, 99,
+    ];
+
+    fn recorded(input: i64) -> Session {
+        let mut output = vec![];
+        let (session, result) = record(
+            &mut Machine::new(COMPARE_TO_8.to_vec()),
+            &mut vec![input],
+            &mut output,
+        );
+        result.unwrap();
+        session
+    }
+
+    #[test]
+    fn test_record() {
+        assert_eq!(
+            recorded(8).events,
+            &[
+                Event::Input { step: 0, value: 8 },
+                Event::Output {
+                    step: 4,
+                    value: 1000
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_save_load_replay() {
+        let mut file = vec![];
+        recorded(3).save(&mut file).unwrap();
+        assert_eq!(
+            String::from_utf8(file.clone()).unwrap(),
+            "intcode-session 1\nin 0 3\nout 5 999\n"
+        );
+        let session = Session::load(&file[..]).unwrap();
+        assert_eq!(
+            replay(&mut Machine::new(COMPARE_TO_8.to_vec()), &session),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_replay_divergence() {
+        // comparing against 3 rather than 8 changes the answer for an input of 3
+        let mut program = COMPARE_TO_8.to_vec();
+        program[4] = 3;
+        assert_eq!(
+            replay(&mut Machine::new(program), &recorded(3)),
+            Err(ReplayError::UnexpectedOutput {
+                found: Event::Output {
+                    step: 4,
+                    value: 375
+                },
+                expected: Some(Event::Output {
+                    step: 5,
+                    value: 999
+                }),
+            })
+        );
+    }
+
+    #[test]
+    fn test_replay_early_halt() {
+        let session = Session {
+            events: vec![Event::Output { step: 0, value: 1 }],
+        };
+        assert_eq!(
+            replay(&mut Machine::new(vec![99]), &session),
+            Err(ReplayError::UnexpectedHalt {
+                step: 0,
+                expected: Event::Output { step: 0, value: 1 }
+            })
+        );
+    }
+
+    /// A buffer which can still be read after a `SessionWriter` takes it.
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_record_to_writes_as_it_goes() {
+        // reads a value, outputs it, then hits an unknown opcode
+        let buffer = Shared::default();
+        let mut writer = SessionWriter::new(buffer.clone());
+        let result = record_to(
+            &mut Machine::new(vec![3, 0, 4, 0, 42]),
+            &mut vec![7],
+            &mut vec![],
+            &mut writer,
+        );
+        assert_eq!(
+            result,
+            Err(IntcodeError::UnknownOpcode {
+                address: 4,
+                opcode: 42
+            })
+        );
+        // everything is written before the writer is finished or dropped
+        assert_eq!(
+            String::from_utf8(buffer.0.borrow().clone()).unwrap(),
+            "intcode-session 1\nin 0 7\nout 1 7\n"
+        );
+        assert!(writer.finish().is_ok());
+    }
+}
@@ -0,0 +1,329 @@
+//! Capturing and restoring the full state of a `Machine`.
+//!
+//! A `Snapshot` holds everything needed to pick a program back up where it
+//! left off: memory, registers, queued input and buffered output. Snapshots
+//! can be kept in memory to fork a machine at interesting points, or saved to
+//! a file as a checkpoint. The file format is plain text:
+//!
+//! ```text
+//! intcode-snapshot 3
+//! ip 4
+//! relative_base 0
+//! steps 1
+//! outputs 0
+//! halted false
+//! input 5,6
+//! output
+//! memory 3,0,4,0,99
+//! ```
+//!
+//! Older files still load. Version 1 has no `outputs` line, so the output
+//! count is taken to be the length of the buffered output, and neither it nor
+//! version 2 has a `halted` line, so the machine is taken to still be running.
+//!
+//! Breakpoint-like configuration such as watchpoints and tracers is not part
+//! of a snapshot, and neither is a status held back behind a watchpoint hit.
+
+use super::Machine;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::str::FromStr;
+
+const HEADER: &str = "intcode-snapshot";
+const VERSION: u32 = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub memory: Vec<i64>,
+    pub ip: usize,
+    pub relative_base: i64,
+    pub steps: u64,
+    /// How many values have been output in total, including those already
+    /// taken, so that an output limit carries over.
+    pub outputs: usize,
+    /// Whether the machine has executed the halt instruction at `ip`.
+    pub halted: bool,
+    pub input: Vec<i64>,
+    pub output: Vec<i64>,
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    /// The file isn't a snapshot, or is from an unknown version.
+    BadHeader,
+    /// A field is missing, or its value can't be parsed.
+    BadField(&'static str),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "{}", e),
+            SnapshotError::BadHeader => write!(f, "not an intcode snapshot"),
+            SnapshotError::BadField(name) => write!(f, "missing or invalid field `{}`", name),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+fn join(values: &[i64]) -> String {
+    let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    values.join(",")
+}
+
+impl Snapshot {
+    /// Writes the snapshot in the text format described in the module docs.
+    pub fn save(&self, mut out: impl Write) -> io::Result<()> {
+        writeln!(out, "{} {}", HEADER, VERSION)?;
+        writeln!(out, "ip {}", self.ip)?;
+        writeln!(out, "relative_base {}", self.relative_base)?;
+        writeln!(out, "steps {}", self.steps)?;
+        writeln!(out, "outputs {}", self.outputs)?;
+        writeln!(out, "halted {}", self.halted)?;
+        writeln!(out, "input {}", join(&self.input))?;
+        writeln!(out, "output {}", join(&self.output))?;
+        writeln!(out, "memory {}", join(&self.memory))?;
+        out.flush()
+    }
+
+    /// Reads a snapshot written by `save`.
+    pub fn load(input: impl Read) -> Result<Self, SnapshotError> {
+        let mut lines = BufReader::new(input).lines();
+        let header = lines.next().transpose()?;
+        let version = header
+            .as_deref()
+            .and_then(|header| header.trim_end().strip_prefix(HEADER))
+            .and_then(|version| version.strip_prefix(' '))
+            .and_then(|version| version.parse::<u32>().ok())
+            .filter(|version| (1..=VERSION).contains(version))
+            .ok_or(SnapshotError::BadHeader)?;
+
+        let mut field = |name: &'static str| -> Result<String, SnapshotError> {
+            let line = lines.next().ok_or(SnapshotError::BadField(name))??;
+            let line = line.trim_end();
+            match line.split_once(' ') {
+                Some((key, value)) if key == name => Ok(value.to_string()),
+                None if line == name => Ok(String::new()),
+                _ => Err(SnapshotError::BadField(name)),
+            }
+        };
+        fn parse<T: FromStr>(name: &'static str, s: &str) -> Result<T, SnapshotError> {
+            s.trim().parse().map_err(|_| SnapshotError::BadField(name))
+        }
+        fn parse_list(name: &'static str, s: &str) -> Result<Vec<i64>, SnapshotError> {
+            if s.trim().is_empty() {
+                return Ok(vec![]);
+            }
+            s.split(',').map(|v| parse(name, v)).collect()
+        }
+
+        let ip = parse("ip", &field("ip")?)?;
+        let relative_base = parse("relative_base", &field("relative_base")?)?;
+        let steps = parse("steps", &field("steps")?)?;
+        let outputs = if version >= 2 {
+            Some(parse("outputs", &field("outputs")?)?)
+        } else {
+            None
+        };
+        let halted = version >= 3 && parse("halted", &field("halted")?)?;
+        let input = parse_list("input", &field("input")?)?;
+        let output = parse_list("output", &field("output")?)?;
+        Ok(Snapshot {
+            ip,
+            relative_base,
+            steps,
+            outputs: outputs.unwrap_or(output.len()),
+            halted,
+            input,
+            output,
+            memory: parse_list("memory", &field("memory")?)?,
+        })
+    }
+}
+
+impl Machine {
+    /// Captures the machine's current state.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            memory: self.memory.clone(),
+            ip: self.ip,
+            relative_base: self.relative_base,
+            steps: self.steps,
+            outputs: self.outputs,
+            halted: self.halted,
+            input: self.input.iter().copied().collect(),
+            output: self.output.clone(),
+        }
+    }
+
+    /// Puts the machine back into the state captured by `snapshot`. Watchpoints,
+    /// limits and any tracer are left in place, and the snapshot's output count
+    /// carries on counting towards an output limit.
+    ///
+    /// Undo history and self-modification tracking describe the timeline
+    /// being left behind, so both are cleared, though they stay enabled.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.memory = snapshot.memory.clone();
+        self.cache.clear();
+        self.ip = snapshot.ip;
+        self.relative_base = snapshot.relative_base;
+        self.steps = snapshot.steps;
+        self.input = snapshot.input.iter().copied().collect();
+        self.output = snapshot.output.clone();
+        self.outputs = snapshot.outputs;
+        self.pending.clear();
+        self.halted = snapshot.halted;
+        if let Some(history) = self.history.as_mut() {
+            history.clear();
+        }
+        if let Some(guard) = self.code_guard.as_mut() {
+            guard.clear();
+        }
+    }
+}
+
+impl From<&Snapshot> for Machine {
+    fn from(snapshot: &Snapshot) -> Self {
+        let mut machine = Machine::new(vec![]);
+        machine.restore(snapshot);
+        machine
+    }
+}
+
+#[cfg(test)]
+mod snapshot_tests {
+    use super::{Snapshot, SnapshotError};
+    use crate::intcode::{Machine, Status};
+
+    #[test]
+    fn test_restore_clears_history() {
+        let mut machine = Machine::new(vec![1101, 1, 1, 10, 99]);
+        machine.enable_history(None);
+        let snapshot = machine.snapshot();
+        let mut other = Machine::new(vec![1101, 2, 2, 20, 1101, 3, 3, 30, 99]);
+        other.enable_history(None);
+        other.run().unwrap();
+
+        other.restore(&snapshot);
+        assert_eq!(other.step_back(), None);
+        assert_eq!(other.run(), Ok(Status::Halted));
+        assert_eq!(other.rewind(5), 2);
+        assert_eq!(other.memory(), &[1101, 1, 1, 10, 99]);
+    }
+
+    #[test]
+    fn test_fork() {
+        let mut machine = Machine::new(vec![3, 0, 4, 0, 99]);
+        assert_eq!(machine.run(), Ok(Status::NeedsInput));
+        let snapshot = machine.snapshot();
+
+        machine.push_input(1);
+        assert_eq!(machine.run(), Ok(Status::Output(1)));
+
+        let mut fork = Machine::from(&snapshot);
+        fork.push_input(2);
+        assert_eq!(fork.run(), Ok(Status::Output(2)));
+
+        machine.restore(&snapshot);
+        machine.push_input(3);
+        assert_eq!(machine.run(), Ok(Status::Output(3)));
+        assert_eq!(machine.output(), &[3]);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        // outputs whatever it reads, then doubles it and outputs that too
+        let mut machine = Machine::new(vec![3, 13, 4, 13, 1002, 13, 2, 13, 4, 13, 99, 0, 0, 0]);
+        machine.push_input(21);
+        machine.push_input(5);
+        assert_eq!(machine.run(), Ok(Status::Output(21)));
+
+        let mut file = vec![];
+        machine.snapshot().save(&mut file).unwrap();
+        let loaded = Snapshot::load(&file[..]).unwrap();
+        assert_eq!(loaded, machine.snapshot());
+        assert_eq!(loaded.input, &[5]);
+        assert_eq!(loaded.output, &[21]);
+
+        let mut restored = Machine::from(&loaded);
+        assert_eq!(restored.run(), Ok(Status::Output(42)));
+        assert_eq!(restored.steps(), machine.steps() + 2);
+    }
+
+    #[test]
+    fn test_halted() {
+        let mut machine = Machine::new(vec![104, 7, 99]);
+        machine.run_with_io(&mut vec![], &mut vec![]).unwrap();
+        let snapshot = machine.snapshot();
+        assert!(snapshot.halted);
+
+        let mut file = vec![];
+        snapshot.save(&mut file).unwrap();
+        assert!(String::from_utf8_lossy(&file).contains("\nhalted true\n"));
+        let loaded = Snapshot::load(&file[..]).unwrap();
+        assert_eq!(loaded, snapshot);
+
+        // halting again isn't counted as another step
+        let mut restored = Machine::from(&loaded);
+        assert_eq!(restored.run(), Ok(Status::Halted));
+        assert_eq!(restored.steps(), 2);
+    }
+
+    #[test]
+    fn test_load_errors() {
+        assert!(matches!(
+            Snapshot::load(&b"hello"[..]),
+            Err(SnapshotError::BadHeader)
+        ));
+        assert!(matches!(
+            Snapshot::load(&b"intcode-snapshot 4\nip 0\n"[..]),
+            Err(SnapshotError::BadHeader)
+        ));
+        assert!(matches!(
+            Snapshot::load(&b"intcode-snapshot\nip 0\n"[..]),
+            Err(SnapshotError::BadHeader)
+        ));
+        assert!(matches!(
+            Snapshot::load(&b"intcode-snapshot 2\nip x\n"[..]),
+            Err(SnapshotError::BadField("ip"))
+        ));
+        assert!(matches!(
+            Snapshot::load(&b"intcode-snapshot 2\nip 0\n"[..]),
+            Err(SnapshotError::BadField("relative_base"))
+        ));
+        assert!(matches!(
+            Snapshot::load(&b"intcode-snapshot 2\nip 0\nrelative_base 0\nsteps 0\ninput\n"[..]),
+            Err(SnapshotError::BadField("outputs"))
+        ));
+        assert!(matches!(
+            Snapshot::load(
+                &b"intcode-snapshot 3\nip 0\nrelative_base 0\nsteps 0\noutputs 0\nhalted 1\n"[..]
+            ),
+            Err(SnapshotError::BadField("halted"))
+        ));
+    }
+
+    #[test]
+    fn test_load_v1() {
+        let file = "intcode-snapshot 1\n\
+                    ip 2\n\
+                    relative_base 0\n\
+                    steps 1\n\
+                    input\n\
+                    output 5\n\
+                    memory 104,5,99\n";
+        let loaded = Snapshot::load(file.as_bytes()).unwrap();
+        assert_eq!(loaded.outputs, 1);
+        assert!(!loaded.halted);
+        assert_eq!(loaded.output, &[5]);
+        assert_eq!(loaded.memory, &[104, 5, 99]);
+    }
+}
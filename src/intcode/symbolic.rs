@@ -0,0 +1,383 @@
+//! Symbolic execution of Day 2 style programs.
+//!
+//! Rather than running a program once per noun and verb, this runs it once
+//! with addresses 1 and 2 holding the symbols `noun` and `verb`, keeping track
+//! of each memory cell as a linear expression over them. When the cell of
+//! interest ends up linear (as the gravity assist program's address 0 does),
+//! any target can be solved for directly.
+//!
+//! The analysis only works while the program's control flow doesn't depend on
+//! the symbols: opcodes, jumps and write addresses all have to be known. A
+//! read from a symbolic address produces an opaque value, which is fine as
+//! long as it's overwritten before it matters. Anything else, including a
+//! product of two symbolic values, makes the analysis give up.
+
+use super::limits::Limits;
+use super::{OpHeader, Param};
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+/// How many instructions to run before deciding the program isn't going to
+/// halt.
+const MAX_STEPS: usize = 1_000_000;
+
+/// The most memory cells to track when `analyze` isn't given a memory limit.
+const MAX_MEMORY: usize = 1 << 20;
+
+/// `noun * noun_coefficient + verb * verb_coefficient + constant`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Linear {
+    pub noun: i64,
+    pub verb: i64,
+    pub constant: i64,
+}
+
+impl Linear {
+    fn constant(value: i64) -> Self {
+        Linear {
+            noun: 0,
+            verb: 0,
+            constant: value,
+        }
+    }
+
+    fn as_constant(&self) -> Option<i64> {
+        if self.noun == 0 && self.verb == 0 {
+            Some(self.constant)
+        } else {
+            None
+        }
+    }
+
+    fn add(self, other: Linear) -> Option<Linear> {
+        Some(Linear {
+            noun: self.noun.checked_add(other.noun)?,
+            verb: self.verb.checked_add(other.verb)?,
+            constant: self.constant.checked_add(other.constant)?,
+        })
+    }
+
+    fn scale(self, factor: i64) -> Option<Linear> {
+        Some(Linear {
+            noun: self.noun.checked_mul(factor)?,
+            verb: self.verb.checked_mul(factor)?,
+            constant: self.constant.checked_mul(factor)?,
+        })
+    }
+
+    /// The expression's value, or `None` if working it out overflows.
+    pub fn eval(&self, noun: i64, verb: i64) -> Option<i64> {
+        self.noun
+            .checked_mul(noun)?
+            .checked_add(self.verb.checked_mul(verb)?)?
+            .checked_add(self.constant)
+    }
+
+    /// Finds the first noun and verb in the given ranges, nouns outermost,
+    /// for which the expression equals `target`. Nouns for which the sum
+    /// overflows are skipped.
+    pub fn solve(
+        &self,
+        target: i64,
+        nouns: &RangeInclusive<i64>,
+        verbs: &RangeInclusive<i64>,
+    ) -> Option<(i64, i64)> {
+        nouns.clone().find_map(|noun| {
+            let rest = target
+                .checked_sub(self.noun.checked_mul(noun)?)?
+                .checked_sub(self.constant)?;
+            let verb = match self.verb {
+                0 if rest == 0 => *verbs.start(),
+                0 => return None,
+                coefficient if rest.checked_rem(coefficient)? == 0 => {
+                    rest.checked_div(coefficient)?
+                }
+                _ => return None,
+            };
+            if verbs.contains(&verb) {
+                Some((noun, verb))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+impl fmt::Display for Linear {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}*noun + {}*verb + {}",
+            self.noun, self.verb, self.constant
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+    Linear(Linear),
+    /// Something the analysis can't describe, like a read from a symbolic
+    /// address.
+    Opaque,
+}
+
+impl Value {
+    fn constant(&self) -> Option<i64> {
+        match self {
+            Value::Linear(linear) => linear.as_constant(),
+            Value::Opaque => None,
+        }
+    }
+
+    fn combine(self, other: Value, f: impl FnOnce(Linear, Linear) -> Option<Linear>) -> Value {
+        match (self, other) {
+            (Value::Linear(a), Value::Linear(b)) => f(a, b).map_or(Value::Opaque, Value::Linear),
+            _ => Value::Opaque,
+        }
+    }
+}
+
+/// A decoded param.
+#[derive(Debug, Clone, Copy)]
+enum Operand {
+    Param(Param),
+    /// An immediate param holding a symbolic value.
+    Symbolic(Value),
+    /// A position or relative param whose address is symbolic.
+    Unknown,
+}
+
+struct Symbolic {
+    memory: Vec<Value>,
+    relative_base: i64,
+    /// Writes at or beyond this address make the analysis give up.
+    memory_limit: usize,
+}
+
+impl Symbolic {
+    fn load(&self, address: usize) -> Value {
+        self.memory
+            .get(address)
+            .copied()
+            .unwrap_or(Value::Linear(Linear::constant(0)))
+    }
+
+    /// Resolves the address a non-immediate param refers to, if it's known.
+    fn address(&self, param: Param) -> Option<usize> {
+        match param {
+            Param::Position(address) => Some(address),
+            Param::Relative(offset) => {
+                usize::try_from(self.relative_base.checked_add(offset)?).ok()
+            }
+            Param::Immediate(_) => None,
+        }
+    }
+
+    /// Decodes the param `n` values past `ip`, given its mode.
+    fn param(&self, ip: usize, n: usize, mode: usize) -> Option<Operand> {
+        let value = self.load(ip + n);
+        match (value.constant(), mode) {
+            (Some(raw), _) => Param::from_pair(ip, (mode, raw)).ok().map(Operand::Param),
+            (None, 1) => Some(Operand::Symbolic(value)),
+            (None, 0) | (None, 2) => Some(Operand::Unknown),
+            (None, _) => None,
+        }
+    }
+
+    fn read(&self, operand: Operand) -> Option<Value> {
+        match operand {
+            Operand::Param(Param::Immediate(value)) => Some(Value::Linear(Linear::constant(value))),
+            Operand::Param(param) => self.address(param).map(|address| self.load(address)),
+            Operand::Symbolic(value) => Some(value),
+            Operand::Unknown => Some(Value::Opaque),
+        }
+    }
+
+    /// Stores `value`, giving up if where it goes isn't known.
+    fn write(&mut self, operand: Operand, value: Value) -> Option<()> {
+        let address = match operand {
+            Operand::Param(param) => self.address(param)?,
+            _ => return None,
+        };
+        if address >= self.memory_limit {
+            return None;
+        }
+        if address >= self.memory.len() {
+            self.memory
+                .resize(address + 1, Value::Linear(Linear::constant(0)));
+        }
+        self.memory[address] = value;
+        Some(())
+    }
+
+    /// Runs the program to completion, returning `None` if it does anything
+    /// the analysis can't follow.
+    fn run(&mut self) -> Option<()> {
+        let mut ip = 0;
+        for _ in 0..MAX_STEPS {
+            let header = OpHeader::try_from(self.load(ip).constant()?).ok()?;
+            let modes = [header.mode1, header.mode2, header.mode3];
+            let param = |n: usize| self.param(ip, n, modes[n - 1]);
+            match header.opcode {
+                1 | 2 | 7 | 8 => {
+                    let (a, b, out) = (param(1)?, param(2)?, param(3)?);
+                    let (a, b) = (self.read(a)?, self.read(b)?);
+                    let value = match header.opcode {
+                        1 => a.combine(b, Linear::add),
+                        2 => a.combine(b, |a, b| match (a.as_constant(), b.as_constant()) {
+                            (Some(a), _) => b.scale(a),
+                            (_, Some(b)) => a.scale(b),
+                            _ => None,
+                        }),
+                        opcode => match (a.constant(), b.constant()) {
+                            (Some(a), Some(b)) if opcode == 7 => {
+                                Value::Linear(Linear::constant((a < b) as i64))
+                            }
+                            (Some(a), Some(b)) => Value::Linear(Linear::constant((a == b) as i64)),
+                            _ => Value::Opaque,
+                        },
+                    };
+                    self.write(out, value)?;
+                    ip += 4;
+                }
+                4 => {
+                    self.read(param(1)?)?;
+                    ip += 2;
+                }
+                5 | 6 => {
+                    let cond = self.read(param(1)?)?.constant()?;
+                    if (cond != 0) == (header.opcode == 5) {
+                        let target = self.read(param(2)?)?.constant()?;
+                        ip = usize::try_from(target).ok()?;
+                    } else {
+                        ip += 3;
+                    }
+                }
+                9 => {
+                    let offset = self.read(param(1)?)?.constant()?;
+                    self.relative_base = self.relative_base.checked_add(offset)?;
+                    ip += 2;
+                }
+                99 => return Some(()),
+                // Input can't be predicted, and anything else is an error.
+                _ => return None,
+            }
+        }
+        None
+    }
+}
+
+/// Runs `program` with symbolic nouns and verbs, returning the value left at
+/// `address` as an expression over them, if it's linear and the program could
+/// be followed within the memory limit in `limits`, or a million or so cells
+/// without one.
+pub fn analyze(program: &[i64], address: usize, limits: Limits) -> Option<Linear> {
+    let mut memory: Vec<Value> = program
+        .iter()
+        .map(|&value| Value::Linear(Linear::constant(value)))
+        .collect();
+    let symbol = |noun, verb| {
+        Value::Linear(Linear {
+            noun,
+            verb,
+            constant: 0,
+        })
+    };
+    *memory.get_mut(1)? = symbol(1, 0);
+    *memory.get_mut(2)? = symbol(0, 1);
+
+    let mut symbolic = Symbolic {
+        memory,
+        relative_base: 0,
+        memory_limit: limits.memory.unwrap_or(MAX_MEMORY),
+    };
+    symbolic.run()?;
+    match symbolic.load(address) {
+        Value::Linear(linear) => Some(linear),
+        Value::Opaque => None,
+    }
+}
+
+#[cfg(test)]
+mod symbolic_tests {
+    use super::{analyze, Linear};
+    use crate::intcode::limits::Limits;
+
+    /// Shaped like the gravity assist program: the first instruction reads
+    /// through the noun and verb, but its result is overwritten, and address
+    /// 0 ends up as `250000 * noun + verb + 12345`.
+    const PROGRAM: [i64; 21] = [
+        1, 0, 0, 3, 2, 1, 20, 19, 1, 19, 2, 19, 1001, 19, 12345, 0, 99, 0, 0, 0, 250000,
+    ];
+
+    #[test]
+    fn test_linear() {
+        let linear = analyze(&PROGRAM, 0, Limits::default()).unwrap();
+        assert_eq!(
+            linear,
+            Linear {
+                noun: 250000,
+                verb: 1,
+                constant: 12345
+            }
+        );
+        assert_eq!(linear.to_string(), "250000*noun + 1*verb + 12345");
+        assert_eq!(linear.eval(12, 34), Some(3012379));
+        assert_eq!(linear.solve(3012379, &(0..=99), &(0..=99)), Some((12, 34)));
+        assert_eq!(linear.solve(3012379, &(0..=99), &(0..=20)), None);
+        assert_eq!(linear.solve(3012380, &(0..=99), &(0..=99)), Some((12, 35)));
+        assert_eq!(linear.eval(i64::MAX, 0), None);
+        assert_eq!(linear.solve(i64::MIN, &(0..=99), &(0..=99)), None);
+        let negated = Linear {
+            noun: 0,
+            verb: -1,
+            constant: 0,
+        };
+        assert_eq!(negated.solve(i64::MIN, &(0..=0), &(0..=99)), None);
+    }
+
+    #[test]
+    fn test_other_cells() {
+        let linear = analyze(&PROGRAM, 19, Limits::default()).unwrap();
+        assert_eq!(linear.eval(1, 1), Some(250001));
+        assert_eq!(
+            analyze(&PROGRAM, 20, Limits::default()).unwrap().eval(5, 5),
+            Some(250000)
+        );
+    }
+
+    #[test]
+    fn test_gives_up() {
+        // Address 3 is read through the noun and verb.
+        assert_eq!(analyze(&PROGRAM, 3, Limits::default()), None);
+        // noun * verb isn't linear.
+        assert_eq!(analyze(&[2, 1, 2, 0, 99], 0, Limits::default()), None);
+        // Input can't be followed, nor can a jump on a symbol.
+        assert_eq!(analyze(&[3, 0, 0, 99], 0, Limits::default()), None);
+        assert_eq!(analyze(&[1105, 0, 0, 99], 0, Limits::default()), None);
+        // Nor can a program which never halts.
+        assert_eq!(
+            analyze(&[1, 0, 0, 3, 1105, 1, 4], 0, Limits::default()),
+            None
+        );
+        // Nor one which writes past the memory limit.
+        let program = [1101, 0, 0, 1000, 99];
+        assert!(analyze(&program, 0, Limits::default()).is_some());
+        assert_eq!(analyze(&program, 0, Limits::default().memory(1000)), None);
+        // Or, without a limit, past what's reasonable to track.
+        assert_eq!(
+            analyze(&[1101, 0, 0, 4000000000000, 99], 0, Limits::default()),
+            None
+        );
+        // Or whose relative base overflows.
+        assert_eq!(
+            analyze(&[109, i64::MAX, 109, 1, 99], 0, Limits::default()),
+            None
+        );
+        assert_eq!(
+            analyze(&[109, i64::MAX, 22101, 0, 1, 1, 99], 0, Limits::default()),
+            None
+        );
+    }
+}
@@ -0,0 +1,279 @@
+//! Records every instruction a `Machine` executes to a log.
+//!
+//! Each executed instruction becomes one line of JSON, for example:
+//!
+//! ```text
+//! {"step":0,"ip":0,"op":"add","params":["[9]","[10]","[3]"],"rb":0,"reads":[[9,30],[10,40]],"writes":[[3,3,70]]}
+//! {"step":2,"ip":8,"op":"out","params":["#7"],"rb":0,"reads":[],"writes":[],"output":7}
+//! ```
+//!
+//! `reads` holds `[address, value]` pairs for params resolved through memory
+//! and `writes` holds `[address, old, new]` triples. Instructions which consume
+//! input or produce output carry an `input` or `output` field, and the final
+//! instruction of a finished program carries `"halted":true`.
+//!
+//! An instruction which fails gets a shorter record with the error in place
+//! of its effects, plus the op if it could be decoded:
+//!
+//! ```text
+//! {"step":1,"ip":4,"op":"add","error":"arithmetic overflow at address 4"}
+//! ```
+
+use super::{IntcodeError, Machine, Op, Status, Step};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Writes trace records to any `Write`.
+///
+/// Write errors don't interrupt the machine. The first one is kept and
+/// reported by `finish`.
+pub struct Tracer {
+    out: Box<dyn Write>,
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    pub fn new(out: impl Write + 'static) -> Self {
+        Tracer {
+            out: Box::new(out),
+            error: None,
+        }
+    }
+
+    /// Creates (or truncates) a trace file at `path`.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Tracer::new(BufWriter::new(File::create(path)?)))
+    }
+
+    /// Appends a record of `step`, the `index`th instruction executed.
+    pub fn record(&mut self, index: u64, step: &Step) {
+        if self.error.is_some() {
+            return;
+        }
+        let line = format_step(index, step);
+        if let Err(e) = writeln!(self.out, "{}", line) {
+            self.error = Some(e);
+        }
+    }
+
+    /// Appends a record of the `index`th instruction, at `address`, failing
+    /// with `error`.
+    pub fn record_error(
+        &mut self,
+        index: u64,
+        address: usize,
+        op: Option<Op>,
+        error: &IntcodeError,
+    ) {
+        if self.error.is_some() {
+            return;
+        }
+        let line = format_error(index, address, op, error);
+        if let Err(e) = writeln!(self.out, "{}", line) {
+            self.error = Some(e);
+        }
+    }
+
+    /// Flushes the log, reporting the first error seen while writing it.
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.out.flush(),
+        }
+    }
+}
+
+/// Renders a single trace record. None of the values need escaping, since
+/// params only ever contain digits, signs, brackets, `#` and `rb`.
+pub fn format_step(index: u64, step: &Step) -> String {
+    let params: Vec<String> = step
+        .op
+        .params()
+        .iter()
+        .map(|p| format!("\"{}\"", p))
+        .collect();
+    let reads: Vec<String> = step
+        .reads
+        .iter()
+        .map(|(address, value)| format!("[{},{}]", address, value))
+        .collect();
+    let writes: Vec<String> = step
+        .writes
+        .iter()
+        .map(|w| format!("[{},{},{}]", w.address, w.old, w.new))
+        .collect();
+
+    let mut line = format!(
+        "{{\"step\":{},\"ip\":{},\"op\":\"{}\",\"params\":[{}],\"rb\":{},\"reads\":[{}],\"writes\":[{}]",
+        index,
+        step.address,
+        step.op.mnemonic(),
+        params.join(","),
+        step.relative_base,
+        reads.join(","),
+        writes.join(",")
+    );
+    if let (Op::Input { .. }, Some(w)) = (step.op, step.writes.first()) {
+        line.push_str(&format!(",\"input\":{}", w.new));
+    }
+    match step.status {
+        Some(Status::Output(value)) => line.push_str(&format!(",\"output\":{}", value)),
+        Some(Status::Halted) => line.push_str(",\"halted\":true"),
+        _ => {}
+    }
+    line.push('}');
+    line
+}
+
+/// Renders the record of a failed instruction. Error messages are plain text
+/// and numbers, so they don't need escaping either.
+pub fn format_error(index: u64, address: usize, op: Option<Op>, error: &IntcodeError) -> String {
+    let op = match op {
+        Some(op) => format!(",\"op\":\"{}\"", op.mnemonic()),
+        None => String::new(),
+    };
+    format!(
+        "{{\"step\":{},\"ip\":{}{},\"error\":\"{}\"}}",
+        index, address, op, error
+    )
+}
+
+/// The machine's tracer, if any. Cloning a machine leaves the clone without a
+/// tracer, since two machines appending to one log would be impossible to
+/// tell apart.
+#[derive(Default)]
+pub(super) struct TraceSlot(Option<Tracer>);
+
+impl TraceSlot {
+    pub(super) fn is_active(&self) -> bool {
+        self.0.is_some()
+    }
+
+    pub(super) fn record(&mut self, index: u64, step: &Step) {
+        if let Some(tracer) = self.0.as_mut() {
+            tracer.record(index, step);
+        }
+    }
+
+    pub(super) fn record_error(
+        &mut self,
+        index: u64,
+        address: usize,
+        op: Option<Op>,
+        error: &IntcodeError,
+    ) {
+        if let Some(tracer) = self.0.as_mut() {
+            tracer.record_error(index, address, op, error);
+        }
+    }
+}
+
+impl Clone for TraceSlot {
+    fn clone(&self) -> Self {
+        TraceSlot(None)
+    }
+}
+
+impl fmt::Debug for TraceSlot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            if self.0.is_some() {
+                "Some(Tracer)"
+            } else {
+                "None"
+            }
+        )
+    }
+}
+
+impl Machine {
+    /// Starts recording each executed instruction to `tracer`.
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = TraceSlot(Some(tracer));
+    }
+
+    /// Stops tracing, handing back the tracer so it can be finished.
+    pub fn take_tracer(&mut self) -> Option<Tracer> {
+        self.tracer.0.take()
+    }
+}
+
+#[cfg(test)]
+mod trace_tests {
+    use super::Tracer;
+    use crate::intcode::Machine;
+    use std::cell::RefCell;
+    use std::io::{self, Write};
+    use std::rc::Rc;
+
+    /// A `Write` whose contents can still be inspected after it's been handed
+    /// to a tracer.
+    #[derive(Clone, Default)]
+    struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_trace() {
+        let buf = SharedBuf::default();
+        let mut machine = Machine::new(vec![3, 9, 1001, 9, 5, 9, 204, 9, 99, 0]);
+        machine.set_tracer(Tracer::new(buf.clone()));
+        machine.push_input(2);
+        while machine.run().unwrap() != crate::intcode::Status::Halted {}
+        machine.take_tracer().unwrap().finish().unwrap();
+
+        let log = String::from_utf8(buf.0.borrow().clone()).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(
+            lines,
+            &[
+                r#"{"step":0,"ip":0,"op":"in","params":["[9]"],"rb":0,"reads":[],"writes":[[9,0,2]],"input":2}"#,
+                r##"{"step":1,"ip":2,"op":"add","params":["[9]","#5","[9]"],"rb":0,"reads":[[9,2]],"writes":[[9,2,7]]}"##,
+                r#"{"step":2,"ip":6,"op":"out","params":["rb+9"],"rb":0,"reads":[[9,7]],"writes":[],"output":7}"#,
+                r#"{"step":3,"ip":8,"op":"hlt","params":[],"rb":0,"reads":[],"writes":[],"halted":true}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trace_error() {
+        let buf = SharedBuf::default();
+        let mut machine = Machine::new(vec![1101, 1, 2, 9, 1101, i64::MAX, 1, 9, 42]);
+        machine.set_tracer(Tracer::new(buf.clone()));
+        assert!(machine.run().is_err());
+        machine.poke(4, 42);
+        assert!(machine.run().is_err());
+        machine.take_tracer().unwrap().finish().unwrap();
+
+        let log = String::from_utf8(buf.0.borrow().clone()).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(
+            lines[1..],
+            [
+                r#"{"step":1,"ip":4,"op":"add","error":"arithmetic overflow at address 4"}"#,
+                r#"{"step":1,"ip":4,"error":"unknown opcode 42 at address 4"}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_clone_drops_tracer() {
+        let mut machine = Machine::new(vec![99]);
+        machine.set_tracer(Tracer::new(io::sink()));
+        let mut clone = machine.clone();
+        assert!(clone.take_tracer().is_none());
+        assert!(machine.take_tracer().is_some());
+    }
+}
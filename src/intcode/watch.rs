@@ -0,0 +1,227 @@
+//! Watchpoints which pause a `Machine` when a memory cell is touched.
+//!
+//! Unlike a breakpoint, which stops at an instruction address, a watchpoint
+//! stops after any instruction that reads or writes the watched cell. This is
+//! handy for programs which keep interesting state (a score, a position) in
+//! fixed locations.
+
+use super::{Machine, Step};
+use std::fmt;
+use std::rc::Rc;
+
+/// The kind of memory access a watchpoint responds to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    /// Either a read or a write.
+    Any,
+}
+
+impl Access {
+    fn matches(self, other: Access) -> bool {
+        self == Access::Any || self == other
+    }
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Access::Read => write!(f, "read"),
+            Access::Write => write!(f, "write"),
+            Access::Any => write!(f, "access"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Watchpoint {
+    pub address: usize,
+    pub access: Access,
+    predicate: Option<Rc<dyn Fn(i64) -> bool>>,
+}
+
+impl Watchpoint {
+    pub fn new(address: usize, access: Access) -> Self {
+        Watchpoint {
+            address,
+            access,
+            predicate: None,
+        }
+    }
+
+    /// Only trigger when the value read, or the new value written, satisfies
+    /// `predicate`.
+    pub fn when(mut self, predicate: impl Fn(i64) -> bool + 'static) -> Self {
+        self.predicate = Some(Rc::new(predicate));
+        self
+    }
+
+    fn triggers(&self, address: usize, access: Access, value: i64) -> bool {
+        self.address == address
+            && self.access.matches(access)
+            && self.predicate.as_ref().map_or(true, |p| p(value))
+    }
+}
+
+impl fmt::Debug for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Watchpoint")
+            .field("address", &self.address)
+            .field("access", &self.access)
+            .field("predicate", &self.predicate.is_some())
+            .finish()
+    }
+}
+
+/// A watchpoint that fired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    /// The watched memory address.
+    pub address: usize,
+    /// Either `Read` or `Write`, never `Any`.
+    pub access: Access,
+    /// The value read, or the new value written.
+    pub value: i64,
+    /// Address of the instruction which touched the cell.
+    pub ip: usize,
+}
+
+impl fmt::Display for WatchHit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} at {} (value {})",
+            self.access, self.address, self.ip, self.value
+        )
+    }
+}
+
+impl Machine {
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    /// Removes every watchpoint on `address`, returning whether any existed.
+    pub fn remove_watchpoints(&mut self, address: usize) -> bool {
+        let before = self.watchpoints.len();
+        self.watchpoints.retain(|w| w.address != address);
+        self.watchpoints.len() != before
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// Finds the watchpoints triggered by the memory accesses in `step`.
+    pub(super) fn check_watchpoints(&self, step: &Step) -> Vec<WatchHit> {
+        if self.watchpoints.is_empty() {
+            return vec![];
+        }
+        let reads = step
+            .reads
+            .iter()
+            .map(|&(address, value)| (address, Access::Read, value));
+        let writes = step
+            .writes
+            .iter()
+            .map(|w| (w.address, Access::Write, w.new));
+        reads
+            .chain(writes)
+            .filter(|&(address, access, value)| {
+                self.watchpoints
+                    .iter()
+                    .any(|w| w.triggers(address, access, value))
+            })
+            .map(|(address, access, value)| WatchHit {
+                address,
+                access,
+                value,
+                ip: step.address,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod watch_tests {
+    use super::{Access, WatchHit, Watchpoint};
+    use crate::intcode::{Machine, Status};
+
+    #[test]
+    fn test_write_watchpoint() {
+        let mut machine = Machine::new(vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+        machine.add_watchpoint(Watchpoint::new(0, Access::Write));
+        assert_eq!(
+            machine.run(),
+            Ok(Status::Watchpoint(WatchHit {
+                address: 0,
+                access: Access::Write,
+                value: 3500,
+                ip: 4,
+            }))
+        );
+        assert_eq!(machine.ip(), 8);
+        assert_eq!(machine.run(), Ok(Status::Halted));
+    }
+
+    #[test]
+    fn test_read_watchpoint() {
+        let mut machine = Machine::new(vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+        machine.add_watchpoint(Watchpoint::new(3, Access::Read));
+        match machine.run() {
+            Ok(Status::Watchpoint(hit)) => assert_eq!((hit.ip, hit.value), (4, 70)),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_predicate() {
+        // counts down from 3 in cell 9, stopping only when it reaches 1
+        let mut machine = Machine::new(vec![1001, 9, -1, 9, 1005, 9, 0, 99, 0, 3]);
+        machine.add_watchpoint(Watchpoint::new(9, Access::Write).when(|v| v == 1));
+        match machine.run() {
+            Ok(Status::Watchpoint(hit)) => assert_eq!(hit.value, 1),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(machine.run(), Ok(Status::Halted));
+    }
+
+    #[test]
+    fn test_output_after_watchpoint() {
+        // the output instruction itself reads the watched cell
+        let mut machine = Machine::new(vec![4, 3, 99, 42]);
+        machine.add_watchpoint(Watchpoint::new(3, Access::Any));
+        assert!(matches!(machine.run(), Ok(Status::Watchpoint(_))));
+        assert_eq!(machine.run(), Ok(Status::Output(42)));
+        assert_eq!(machine.run(), Ok(Status::Halted));
+    }
+
+    #[test]
+    fn test_every_hit_reported() {
+        // reads 5 twice, then writes it, before halting
+        let mut machine = Machine::new(vec![1, 5, 5, 5, 99, 21]);
+        machine.add_watchpoint(Watchpoint::new(5, Access::Any));
+        let hit = |access, value| {
+            Ok(Status::Watchpoint(WatchHit {
+                address: 5,
+                access,
+                value,
+                ip: 0,
+            }))
+        };
+        assert_eq!(machine.run(), hit(Access::Read, 21));
+        assert_eq!(machine.run(), hit(Access::Read, 21));
+        assert_eq!(machine.run(), hit(Access::Write, 42));
+        assert_eq!(machine.run(), Ok(Status::Halted));
+    }
+
+    #[test]
+    fn test_remove_watchpoints() {
+        let mut machine = Machine::new(vec![1101, 1, 1, 5, 99, 0]);
+        machine.add_watchpoint(Watchpoint::new(5, Access::Write));
+        assert!(machine.remove_watchpoints(5));
+        assert!(!machine.remove_watchpoints(5));
+        assert_eq!(machine.run(), Ok(Status::Halted));
+    }
+}
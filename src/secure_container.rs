@@ -53,7 +53,7 @@ fn common_validation(digits: &[u32]) -> bool {
     if digits != sorted.as_slice() {
         return false;
     }
-    return true;
+    true
 }
 
 /// Checks to see if a number conforms to the password policy outlined in part 1.
@@ -70,7 +70,7 @@ pub fn is_valid(n: u32) -> bool {
         prev = *i as i32;
     }
 
-    return false;
+    false
 }
 
 /// Checks to see if a number conforms to the password policy outlined in part 2.
@@ -95,7 +95,7 @@ pub fn is_more_valid(n: u32) -> bool {
         }
     }
 
-    return false;
+    false
 }
 
 /// Convert a non-negative number into a series of individual digits.